@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use avian3d::prelude::*;
+use bevy::{gltf::Gltf, prelude::*};
+use bevy_tnua::{builtins::TnuaBuiltinCrouch, prelude::*};
+
+use crate::GameState;
+use crate::gameplay::enemies::melee_creep::Enemy;
+use crate::gameplay::traversal::DASH;
+use crate::gameplay::water::Swimming;
+use crate::player::Health;
+
+/// Seconds to cross-fade from one clip to the next
+const BLEND_TIME: f32 = 0.15;
+/// Horizontal speed below which a character counts as standing still
+const IDLE_SPEED: f32 = 0.2;
+/// Chip damage like drowning doesn't make characters flinch
+const MIN_HIT_DAMAGE: f32 = 5.0;
+
+pub struct CharacterAnimationPlugin;
+
+impl Plugin for CharacterAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CharacterRig>()
+            .add_event::<CastStarted>()
+            .add_event::<CastReleased>()
+            .add_observer(forward_cast_release)
+            .add_systems(
+                Update,
+                (
+                    load_rigs,
+                    build_rigs,
+                    attach_animation_players,
+                    start_casts,
+                    drive_animations,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Rigged glTF shown in place of a character's placeholder mesh. Clips are looked
+/// up by name (see [`CharacterClip`]), anything missing falls back to a similar clip.
+/// The stand-in rigs in `assets/characters` come from `tools/make_rig.py`.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+pub struct CharacterRig {
+    pub path: String,
+    /// Where the model's feet go relative to the collider's centre
+    pub offset: Vec3,
+    /// Speed the walk clip was authored at
+    pub walk_speed: f32,
+    /// Speed the run clip was authored at, walk and run blend in between
+    pub run_speed: f32,
+    /// Seconds into the cast clip that the spell leaves the hand
+    pub cast_release: f32,
+}
+
+impl Default for CharacterRig {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            offset: Vec3::new(0.0, -1.0, 0.0),
+            walk_speed: 3.0,
+            run_speed: 10.0,
+            cast_release: 0.25,
+        }
+    }
+}
+
+impl CharacterRig {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            ..default()
+        }
+    }
+}
+
+/// Clips a character rig can have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CharacterClip {
+    Idle,
+    Walk,
+    Run,
+    Jump,
+    Fall,
+    Crouch,
+    Dash,
+    Swim,
+    Cast,
+    Hit,
+    Death,
+}
+
+impl CharacterClip {
+    const ALL: [CharacterClip; 11] = [
+        CharacterClip::Idle,
+        CharacterClip::Walk,
+        CharacterClip::Run,
+        CharacterClip::Jump,
+        CharacterClip::Fall,
+        CharacterClip::Crouch,
+        CharacterClip::Dash,
+        CharacterClip::Swim,
+        CharacterClip::Cast,
+        CharacterClip::Hit,
+        CharacterClip::Death,
+    ];
+
+    /// Animation name to look for in the glTF
+    pub fn name(self) -> &'static str {
+        match self {
+            CharacterClip::Idle => "Idle",
+            CharacterClip::Walk => "Walk",
+            CharacterClip::Run => "Run",
+            CharacterClip::Jump => "Jump",
+            CharacterClip::Fall => "Fall",
+            CharacterClip::Crouch => "Crouch",
+            CharacterClip::Dash => "Dash",
+            CharacterClip::Swim => "Swim",
+            CharacterClip::Cast => "Cast",
+            CharacterClip::Hit => "Hit",
+            CharacterClip::Death => "Death",
+        }
+    }
+
+    /// What to play instead when the rig doesn't have this clip
+    fn fallback(self) -> Option<CharacterClip> {
+        match self {
+            CharacterClip::Idle => None,
+            CharacterClip::Walk => Some(CharacterClip::Idle),
+            CharacterClip::Run => Some(CharacterClip::Walk),
+            CharacterClip::Jump => Some(CharacterClip::Fall),
+            CharacterClip::Fall => Some(CharacterClip::Idle),
+            CharacterClip::Crouch => Some(CharacterClip::Idle),
+            CharacterClip::Dash => Some(CharacterClip::Run),
+            CharacterClip::Swim => Some(CharacterClip::Fall),
+            CharacterClip::Cast | CharacterClip::Hit | CharacterClip::Death => None,
+        }
+    }
+
+    fn looping(self) -> bool {
+        !matches!(
+            self,
+            CharacterClip::Jump | CharacterClip::Cast | CharacterClip::Hit | CharacterClip::Death
+        )
+    }
+}
+
+/// A character wants to cast. Played as an animation if the rig has one.
+#[derive(Event)]
+pub struct CastStarted(pub Entity);
+
+/// The cast has reached the frame where the spell leaves the hand.
+#[derive(Event)]
+pub struct CastReleased(pub Entity);
+
+/// Animation event keyed into the cast clip, triggered on the `AnimationPlayer`.
+#[derive(Event, Clone)]
+struct CastRelease;
+
+#[derive(Component)]
+struct RigLoading(Handle<Gltf>);
+
+/// Built once the rig's glTF has loaded.
+#[derive(Component)]
+pub struct RigAnimations {
+    graph: Handle<AnimationGraph>,
+    nodes: HashMap<CharacterClip, AnimationNodeIndex>,
+    /// Entity in the spawned scene holding the `AnimationPlayer`
+    player: Option<Entity>,
+    one_shot: Option<CharacterClip>,
+    /// A cast came in that still has to be started on the player
+    cast_pending: bool,
+    last_health: Option<f32>,
+}
+
+impl RigAnimations {
+    fn node(&self, clip: CharacterClip) -> Option<AnimationNodeIndex> {
+        let mut clip = Some(clip);
+        while let Some(current) = clip {
+            if let Some(node) = self.nodes.get(&current) {
+                return Some(*node);
+            }
+            clip = current.fallback();
+        }
+        None
+    }
+}
+
+/// Points from an `AnimationPlayer` back to the character it animates.
+#[derive(Component)]
+struct RigOf(Entity);
+
+fn load_rigs(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    query: Query<(Entity, &CharacterRig), Added<CharacterRig>>,
+) {
+    for (entity, rig) in &query {
+        commands
+            .entity(entity)
+            .insert(RigLoading(asset_server.load(&rig.path)));
+    }
+}
+
+fn build_rigs(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    gltfs: Res<Assets<Gltf>>,
+    mut clips: ResMut<Assets<AnimationClip>>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    query: Query<(Entity, &CharacterRig, &RigLoading, Option<&Children>)>,
+    placeholder_query: Query<(), With<Mesh3d>>,
+) {
+    for (entity, rig, loading, children) in &query {
+        let Some(gltf) = gltfs.get(&loading.0) else {
+            if asset_server.load_state(&loading.0).is_failed() {
+                warn!(
+                    "Couldn't load character rig {}, keeping the placeholder",
+                    rig.path
+                );
+                commands.entity(entity).remove::<RigLoading>();
+            }
+            continue;
+        };
+        let Some(scene) = gltf.default_scene.clone().or(gltf.scenes.first().cloned()) else {
+            warn!("Character rig {} has no scene", rig.path);
+            commands.entity(entity).remove::<RigLoading>();
+            continue;
+        };
+
+        let mut graph = AnimationGraph::new();
+        let mut nodes = HashMap::new();
+        for clip in CharacterClip::ALL {
+            let Some(handle) = gltf
+                .named_animations
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(clip.name()))
+                .map(|(_, handle)| handle.clone())
+            else {
+                continue;
+            };
+            // Each rig gets its own copy of the cast so the release can be timed per character
+            let handle = match clips.get(&handle) {
+                Some(source) if clip == CharacterClip::Cast => {
+                    let mut cast = source.clone();
+                    cast.add_event(rig.cast_release, CastRelease);
+                    clips.add(cast)
+                }
+                _ => handle,
+            };
+            nodes.insert(clip, graph.add_clip(handle, 1.0, graph.root));
+        }
+        if nodes.is_empty() {
+            warn!("Character rig {} has no clips we recognise", rig.path);
+        }
+
+        // Hide whatever stood in for the model until now
+        commands
+            .entity(entity)
+            .remove::<(RigLoading, Mesh3d, MeshMaterial3d<StandardMaterial>)>()
+            .insert(RigAnimations {
+                graph: graphs.add(graph),
+                nodes,
+                player: None,
+                one_shot: None,
+                cast_pending: false,
+                last_health: None,
+            })
+            .with_child((
+                SceneRoot(scene),
+                // glTF characters face +Z, Bevy's forward is -Z
+                Transform::from_translation(rig.offset).with_rotation(Quat::from_rotation_y(PI)),
+            ));
+        for child in children.into_iter().flatten() {
+            if placeholder_query.contains(*child) {
+                commands.entity(*child).insert(Visibility::Hidden);
+            }
+        }
+    }
+}
+
+/// The `AnimationPlayer` turns up somewhere inside the rig's scene once it spawns.
+fn attach_animation_players(
+    mut commands: Commands,
+    new_players: Query<Entity, Added<AnimationPlayer>>,
+    parents: Query<&ChildOf>,
+    mut rig_query: Query<&mut RigAnimations>,
+) {
+    for entity in &new_players {
+        let Some(root) = parents
+            .iter_ancestors(entity)
+            .find(|ancestor| rig_query.contains(*ancestor))
+        else {
+            continue;
+        };
+        let Ok(mut rig) = rig_query.get_mut(root) else {
+            continue;
+        };
+        rig.player = Some(entity);
+        commands
+            .entity(entity)
+            .insert((AnimationGraphHandle(rig.graph.clone()), RigOf(root)));
+    }
+}
+
+/// Characters without a cast animation let go of the spell straight away.
+pub(crate) fn start_casts(
+    mut cast_started: EventReader<CastStarted>,
+    mut cast_released: EventWriter<CastReleased>,
+    mut rig_query: Query<&mut RigAnimations>,
+) {
+    for CastStarted(caster) in cast_started.read() {
+        match rig_query.get_mut(*caster) {
+            Ok(mut rig) if rig.player.is_some() && rig.nodes.contains_key(&CharacterClip::Cast) => {
+                rig.one_shot = Some(CharacterClip::Cast);
+                rig.cast_pending = true;
+            }
+            _ => {
+                cast_released.write(CastReleased(*caster));
+            }
+        }
+    }
+}
+
+fn forward_cast_release(
+    trigger: Trigger<CastRelease>,
+    rig_of: Query<&RigOf>,
+    mut cast_released: EventWriter<CastReleased>,
+) {
+    if let Ok(RigOf(root)) = rig_of.get(trigger.target()) {
+        cast_released.write(CastReleased(*root));
+    }
+}
+
+/// Picks what each character should be playing from its gameplay state and
+/// cross-fades toward it.
+fn drive_animations(
+    time: Res<Time>,
+    mut cast_released: EventWriter<CastReleased>,
+    mut rig_query: Query<(
+        Entity,
+        &mut RigAnimations,
+        &CharacterRig,
+        &LinearVelocity,
+        Option<&TnuaController>,
+        Option<&Health>,
+        Option<&Enemy>,
+        Has<Swimming>,
+    )>,
+    mut player_query: Query<&mut AnimationPlayer>,
+) {
+    let blend = time.delta_secs() / BLEND_TIME;
+    for (entity, mut rig, settings, velocity, controller, health, enemy, swimming) in &mut rig_query
+    {
+        let Some(mut player) = rig
+            .player
+            .and_then(|entity| player_query.get_mut(entity).ok())
+        else {
+            // Nothing to play the cast on, don't swallow the spell
+            if rig.cast_pending {
+                rig.cast_pending = false;
+                rig.one_shot = None;
+                cast_released.write(CastReleased(entity));
+            }
+            continue;
+        };
+
+        // --- HIT/DEATH ---
+        let health = health
+            .map(|health| health.0)
+            .or(enemy.map(|enemy| enemy.health));
+        let dead = health.is_some_and(|health| health <= 0.0);
+        let hurt = matches!((health, rig.last_health), (Some(now), Some(before)) if before - now >= MIN_HIT_DAMAGE);
+        rig.last_health = health;
+        let started = if dead {
+            (rig.one_shot != Some(CharacterClip::Death)).then_some(CharacterClip::Death)
+        } else if hurt && rig.one_shot != Some(CharacterClip::Cast) {
+            Some(CharacterClip::Hit)
+        } else if rig.one_shot == Some(CharacterClip::Death) {
+            // Back from the dead, e.g. after a respawn
+            rig.one_shot = None;
+            None
+        } else {
+            None
+        };
+        if let Some(clip) = started {
+            rig.one_shot = Some(clip);
+            if let Some(node) = rig.node(clip) {
+                player.play(node).replay();
+            }
+        }
+        // Casts are started by start_casts, which can't reach the player. Always from
+        // the top, even if the last cast is still playing or fading out, or the
+        // release would never come. Dying on the same frame drops it.
+        if std::mem::take(&mut rig.cast_pending) && rig.one_shot == Some(CharacterClip::Cast) {
+            match rig.node(CharacterClip::Cast) {
+                Some(node) => {
+                    player.play(node).replay();
+                }
+                None => {
+                    rig.one_shot = None;
+                    cast_released.write(CastReleased(entity));
+                }
+            }
+        }
+        // Let one-shots finish, except death which holds its last frame
+        if let Some(clip) = rig.one_shot
+            && clip != CharacterClip::Death
+            && rig
+                .node(clip)
+                .and_then(|node| player.animation(node))
+                .is_none_or(|active| active.is_finished())
+        {
+            rig.one_shot = None;
+        }
+
+        // --- TARGET WEIGHTS ---
+        let mut targets: HashMap<AnimationNodeIndex, f32> = HashMap::new();
+        let mut target = |clip: CharacterClip, weight: f32| {
+            if let Some(node) = rig.node(clip) {
+                *targets.entry(node).or_default() += weight;
+            }
+        };
+        let speed = velocity.xz().length();
+        let airborne = controller
+            .and_then(|controller| controller.is_airborne().ok())
+            .unwrap_or(velocity.y < -2.0);
+        let action = controller.and_then(|controller| controller.action_name());
+
+        if let Some(clip) = rig.one_shot {
+            target(clip, 1.0);
+        } else if swimming {
+            target(CharacterClip::Swim, 1.0);
+        } else if action == Some(DASH) {
+            target(CharacterClip::Dash, 1.0);
+        } else if airborne {
+            if velocity.y > 0.0 {
+                target(CharacterClip::Jump, 1.0);
+            } else {
+                target(CharacterClip::Fall, 1.0);
+            }
+        } else if action == Some(TnuaBuiltinCrouch::NAME) {
+            target(CharacterClip::Crouch, 1.0);
+        } else if speed < IDLE_SPEED {
+            target(CharacterClip::Idle, 1.0);
+        } else {
+            // Blend walk into run by how fast Tnua is actually moving us
+            let run = ((speed - settings.walk_speed)
+                / (settings.run_speed - settings.walk_speed).max(0.01))
+            .clamp(0.0, 1.0);
+            let moving = (speed / settings.walk_speed.max(0.01)).clamp(0.0, 1.0);
+            target(CharacterClip::Idle, 1.0 - moving);
+            target(CharacterClip::Walk, moving * (1.0 - run));
+            target(CharacterClip::Run, moving * run);
+        }
+
+        // --- CROSS-FADE ---
+        for clip in CharacterClip::ALL {
+            let Some(node) = rig.node(clip) else {
+                continue;
+            };
+            // Fallbacks share nodes, only handle each one once
+            if rig.nodes.get(&clip) != Some(&node) {
+                continue;
+            }
+            let goal = targets.get(&node).copied().unwrap_or(0.0).min(1.0);
+            let weight = player.animation(node).map_or(0.0, |active| active.weight());
+            let weight = weight + (goal - weight).clamp(-blend, blend);
+            if weight <= 0.0 && goal <= 0.0 {
+                player.stop(node);
+                continue;
+            }
+            let active = player.play(node);
+            if clip.looping() {
+                active.repeat();
+            }
+            active.set_weight(weight);
+        }
+    }
+}
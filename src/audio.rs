@@ -0,0 +1,610 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use avian3d::prelude::*;
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    audio::{AddAudioSource, Decodable, Source, SpatialScale, Volume},
+    prelude::*,
+};
+use bevy_tnua::{TnuaProximitySensor, prelude::*};
+use serde::Deserialize;
+
+use crate::animation::CastReleased;
+use crate::camera::ThirdPersonCamera;
+use crate::gameplay::attacks::fireball::FireballImpact;
+use crate::gameplay::elements::DamageDealt;
+use crate::gameplay::enemies::melee_creep::{Dead, Enemy, EnemyKilled};
+use crate::gameplay::traversal::DashStarted;
+use crate::gameplay::water::Swimming;
+use crate::player::Player;
+use crate::{GameState, SpikeHit};
+
+const SAMPLE_RATE: u32 = 44_100;
+/// Shrinks world distances for rodio, which falls off with the square of distance.
+/// At this scale a sound is full volume within ~7 m and a tenth of it at ~20 m.
+const SPATIAL_SCALE: f32 = 0.15;
+/// How far either way a sound effect's pitch gets nudged so repeats don't grate
+const PITCH_VARIANCE: f32 = 0.15;
+/// Metres walked per footstep
+const STRIDE: f32 = 1.6;
+/// Upward speed that counts as a jump rather than walking off a ledge
+const JUMP_SPEED: f32 = 1.0;
+/// Enemies closer than this count as a fight for the music
+const COMBAT_RANGE: f32 = 20.0;
+/// Enemies in range at once for the music to be all combat
+const FULL_COMBAT_ENEMIES: f32 = 3.0;
+/// Seconds for the music to go fully from one layer to the other
+const CROSSFADE_SECONDS: f32 = 2.0;
+
+/// Sound effects and music. Leaves itself out if Bevy's `AudioPlugin` isn't there,
+/// see `NO_AUDIO` in main.
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<(AudioSettings, GroundMaterial)>()
+            .init_resource::<AudioSettings>();
+        if !app.is_plugin_added::<bevy::audio::AudioPlugin>() {
+            info!("Audio disabled");
+            return;
+        }
+        app.add_audio_source::<Synth>()
+            .init_asset_loader::<SynthLoader>()
+            .add_event::<PlaySfx>()
+            .add_systems(Startup, load_sound_bank)
+            .add_systems(OnEnter(GameState::InGame), spawn_music)
+            .add_systems(
+                Update,
+                (
+                    (
+                        attach_listener,
+                        combat_sounds,
+                        movement_sounds,
+                        fireball_sounds,
+                    ),
+                    play_sfx,
+                    expire_sfx,
+                    crossfade_music,
+                    apply_master_volume,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct AudioSettings {
+    /// Everything, on top of the two below
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 0.8,
+            music: 0.5,
+            sfx: 1.0,
+        }
+    }
+}
+
+/// What a floor is made of, for footsteps. Untagged floors count as stone.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[reflect(Component, Default)]
+pub enum GroundMaterial {
+    #[default]
+    Stone,
+    Grass,
+    Wood,
+    Metal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sfx {
+    FireballCast,
+    FireballImpact,
+    Jump,
+    Land,
+    Dash,
+    SpikeHit,
+    CreepHit,
+    CreepDeath,
+    Footstep(GroundMaterial),
+}
+
+impl Sfx {
+    const ALL: [Sfx; 12] = [
+        Sfx::FireballCast,
+        Sfx::FireballImpact,
+        Sfx::Jump,
+        Sfx::Land,
+        Sfx::Dash,
+        Sfx::SpikeHit,
+        Sfx::CreepHit,
+        Sfx::CreepDeath,
+        Sfx::Footstep(GroundMaterial::Stone),
+        Sfx::Footstep(GroundMaterial::Grass),
+        Sfx::Footstep(GroundMaterial::Wood),
+        Sfx::Footstep(GroundMaterial::Metal),
+    ];
+
+    fn path(self) -> String {
+        let name = match self {
+            Sfx::FireballCast => "fireball_cast".to_string(),
+            Sfx::FireballImpact => "fireball_impact".to_string(),
+            Sfx::Jump => "jump".to_string(),
+            Sfx::Land => "land".to_string(),
+            Sfx::Dash => "dash".to_string(),
+            Sfx::SpikeHit => "spike_hit".to_string(),
+            Sfx::CreepHit => "creep_hit".to_string(),
+            Sfx::CreepDeath => "creep_death".to_string(),
+            Sfx::Footstep(ground) => format!("step_{}", format!("{ground:?}").to_lowercase()),
+        };
+        format!("audio/{name}.synth.ron")
+    }
+}
+
+/// Play a sound effect, from a point in the world or straight in the player's ears.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PlaySfx {
+    pub sfx: Sfx,
+    pub position: Option<Vec3>,
+}
+
+impl PlaySfx {
+    pub fn at(sfx: Sfx, position: Vec3) -> Self {
+        Self {
+            sfx,
+            position: Some(position),
+        }
+    }
+}
+
+/// A sound made up in code from a few tones and some noise, read from
+/// `assets/audio/<id>.synth.ron`. Rendered once on load.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct Synth {
+    samples: Arc<[f32]>,
+}
+
+impl Synth {
+    fn duration(&self) -> Duration {
+        Duration::from_secs_f32(self.samples.len() as f32 / SAMPLE_RATE as f32)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SynthDefinition {
+    /// Mixed together, each starting at the beginning
+    voices: Vec<Voice>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Voice {
+    wave: Wave,
+    #[serde(default = "full_volume")]
+    volume: f32,
+    /// Seconds each note takes to fade in
+    #[serde(default)]
+    attack: f32,
+    /// Seconds each note takes to fade out
+    #[serde(default)]
+    release: f32,
+    /// Played one after another
+    notes: Vec<Note>,
+}
+
+fn full_volume() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+enum Wave {
+    Sine,
+    Square,
+    Saw,
+    Triangle,
+    /// Picks a new random level `freq` times a second, so higher is hissier
+    Noise,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+struct Note {
+    /// Hz, 0 for a rest
+    freq: f32,
+    /// Slides to this by the end of the note
+    #[serde(default)]
+    slide_to: Option<f32>,
+    seconds: f32,
+}
+
+impl SynthDefinition {
+    fn render(&self) -> Vec<f32> {
+        let rate = SAMPLE_RATE as f32;
+        let length = self
+            .voices
+            .iter()
+            .map(|voice| voice.notes.iter().map(|note| note.seconds).sum::<f32>())
+            .fold(0.0, f32::max);
+        let mut samples = vec![0.0; (length * rate) as usize];
+        // Same noise every time, so a sound always sounds the same
+        let mut rng = fastrand::Rng::with_seed(0x5eed);
+
+        for voice in &self.voices {
+            let mut start = 0;
+            let mut phase = 0.0_f32;
+            let mut held = 0.0;
+            for note in &voice.notes {
+                let count = (note.seconds * rate) as usize;
+                for index in 0..count.min(samples.len().saturating_sub(start)) {
+                    let progress = index as f32 / count as f32;
+                    let freq = note.freq.lerp(note.slide_to.unwrap_or(note.freq), progress);
+                    let last = phase;
+                    phase = (phase + freq / rate).fract();
+                    if note.freq <= 0.0 {
+                        continue;
+                    }
+                    let wave = match voice.wave {
+                        Wave::Sine => (phase * std::f32::consts::TAU).sin(),
+                        Wave::Square => {
+                            if phase < 0.5 {
+                                1.0
+                            } else {
+                                -1.0
+                            }
+                        }
+                        Wave::Saw => phase * 2.0 - 1.0,
+                        Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                        Wave::Noise => {
+                            if phase < last {
+                                held = rng.f32() * 2.0 - 1.0;
+                            }
+                            held
+                        }
+                    };
+                    let elapsed = index as f32 / rate;
+                    let left = (count - index) as f32 / rate;
+                    let envelope = (elapsed / voice.attack.max(0.001))
+                        .min(left / voice.release.max(0.001))
+                        .min(1.0);
+                    samples[start + index] += wave * envelope * voice.volume;
+                }
+                start += count;
+            }
+        }
+        for sample in &mut samples {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+        samples
+    }
+}
+
+#[derive(Default)]
+struct SynthLoader;
+
+impl AssetLoader for SynthLoader {
+    type Asset = Synth;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let definition: SynthDefinition = ron::de::from_bytes(&bytes)?;
+        Ok(Synth {
+            samples: definition.render().into(),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["synth.ron"]
+    }
+}
+
+impl Decodable for Synth {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthDecoder {
+            samples: self.samples.clone(),
+            next: 0,
+        }
+    }
+}
+
+pub struct SynthDecoder {
+    samples: Arc<[f32]>,
+    next: usize,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.next).copied();
+        self.next += 1;
+        sample
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len().saturating_sub(self.next))
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(
+            self.samples.len() as f32 / SAMPLE_RATE as f32,
+        ))
+    }
+}
+
+#[derive(Resource)]
+struct SoundBank {
+    sfx: HashMap<Sfx, Handle<Synth>>,
+    exploration: Handle<Synth>,
+    combat: Handle<Synth>,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum MusicLayer {
+    Exploration,
+    Combat,
+}
+
+/// Cleans up a sound effect even if it never got to play, e.g. with no audio device.
+#[derive(Component)]
+struct SfxLifetime(Timer);
+
+/// Per-entity bookkeeping for jump, land and footstep sounds.
+#[derive(Component, Default)]
+struct StepTracker {
+    walked: f32,
+    airborne: bool,
+}
+
+fn load_sound_bank(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SoundBank {
+        sfx: Sfx::ALL
+            .iter()
+            .map(|sfx| (*sfx, asset_server.load(sfx.path())))
+            .collect(),
+        exploration: asset_server.load("audio/music_exploration.synth.ron"),
+        combat: asset_server.load("audio/music_combat.synth.ron"),
+    });
+}
+
+fn spawn_music(mut commands: Commands, bank: Res<SoundBank>) {
+    for (layer, handle) in [
+        (MusicLayer::Exploration, &bank.exploration),
+        (MusicLayer::Combat, &bank.combat),
+    ] {
+        // Both always run so they stay in step, crossfade_music decides what's heard
+        commands.spawn((
+            AudioPlayer(handle.clone()),
+            PlaybackSettings::LOOP.with_volume(Volume::SILENT),
+            layer,
+        ));
+    }
+}
+
+/// Ears go on the camera, and the player gets footsteps.
+fn attach_listener(
+    mut commands: Commands,
+    camera_query: Query<Entity, Added<ThirdPersonCamera>>,
+    player_query: Query<Entity, Added<Player>>,
+) {
+    for camera in &camera_query {
+        commands.entity(camera).insert(SpatialListener::new(0.3));
+    }
+    for player in &player_query {
+        commands.entity(player).insert(StepTracker::default());
+    }
+}
+
+fn combat_sounds(
+    mut dealt: EventReader<DamageDealt>,
+    mut killed: EventReader<EnemyKilled>,
+    mut spike_hits: EventReader<SpikeHit>,
+    enemy_query: Query<&GlobalTransform, With<Enemy>>,
+    mut play: EventWriter<PlaySfx>,
+) {
+    for hit in dealt.read() {
+        if let Ok(transform) = enemy_query.get(hit.target) {
+            play.write(PlaySfx::at(Sfx::CreepHit, transform.translation()));
+        }
+    }
+    for kill in killed.read() {
+        play.write(PlaySfx::at(Sfx::CreepDeath, kill.position));
+    }
+    for SpikeHit(position) in spike_hits.read() {
+        play.write(PlaySfx::at(Sfx::SpikeHit, *position));
+    }
+}
+
+fn fireball_sounds(
+    mut cast_released: EventReader<CastReleased>,
+    mut impacts: EventReader<FireballImpact>,
+    caster_query: Query<&GlobalTransform>,
+    mut play: EventWriter<PlaySfx>,
+) {
+    for CastReleased(caster) in cast_released.read() {
+        if let Ok(transform) = caster_query.get(*caster) {
+            play.write(PlaySfx::at(Sfx::FireballCast, transform.translation()));
+        }
+    }
+    for impact in impacts.read() {
+        play.write(PlaySfx::at(Sfx::FireballImpact, impact.position));
+    }
+}
+
+/// Walks up the hierarchy since the collider is often a child of the tagged floor.
+fn ground_material(
+    mut entity: Entity,
+    materials: &Query<&GroundMaterial>,
+    parents: &Query<&ChildOf>,
+) -> GroundMaterial {
+    loop {
+        if let Ok(material) = materials.get(entity) {
+            return *material;
+        }
+        let Ok(parent) = parents.get(entity) else {
+            return GroundMaterial::default();
+        };
+        entity = parent.parent();
+    }
+}
+
+fn movement_sounds(
+    time: Res<Time>,
+    mut dashed: EventReader<DashStarted>,
+    mut player_query: Query<(
+        &GlobalTransform,
+        &TnuaController,
+        &TnuaProximitySensor,
+        &LinearVelocity,
+        &mut StepTracker,
+        Has<Swimming>,
+    )>,
+    materials: Query<&GroundMaterial>,
+    parents: Query<&ChildOf>,
+    mut play: EventWriter<PlaySfx>,
+) {
+    for DashStarted(entity) in dashed.read() {
+        if let Ok((transform, ..)) = player_query.get(*entity) {
+            play.write(PlaySfx::at(Sfx::Dash, transform.translation()));
+        }
+    }
+
+    for (transform, controller, sensor, velocity, mut tracker, swimming) in &mut player_query {
+        let feet = transform.translation();
+        let airborne = controller.is_airborne().unwrap_or(false) || swimming;
+        if airborne != tracker.airborne {
+            tracker.airborne = airborne;
+            tracker.walked = 0.0;
+            if !swimming {
+                if !airborne {
+                    play.write(PlaySfx::at(Sfx::Land, feet));
+                } else if velocity.y > JUMP_SPEED {
+                    play.write(PlaySfx::at(Sfx::Jump, feet));
+                }
+            }
+        }
+        if airborne {
+            continue;
+        }
+
+        tracker.walked += velocity.xz().length() * time.delta_secs();
+        if tracker.walked >= STRIDE {
+            tracker.walked -= STRIDE;
+            let ground = sensor
+                .output
+                .as_ref()
+                .map(|ground| ground_material(ground.entity, &materials, &parents))
+                .unwrap_or_default();
+            play.write(PlaySfx::at(Sfx::Footstep(ground), feet));
+        }
+    }
+}
+
+fn play_sfx(
+    mut commands: Commands,
+    mut play: EventReader<PlaySfx>,
+    settings: Res<AudioSettings>,
+    bank: Res<SoundBank>,
+    synths: Res<Assets<Synth>>,
+) {
+    for request in play.read() {
+        let Some(handle) = bank.sfx.get(&request.sfx) else {
+            continue;
+        };
+        // Not loaded yet, too late to matter by the time it is
+        let Some(synth) = synths.get(handle) else {
+            continue;
+        };
+        let speed = 1.0 + (fastrand::f32() - 0.5) * PITCH_VARIANCE * 2.0;
+        let playback = PlaybackSettings::DESPAWN
+            .with_volume(Volume::Linear(settings.sfx))
+            .with_speed(speed);
+        let lifetime = SfxLifetime(Timer::new(
+            synth.duration().div_f32(speed) + Duration::from_millis(500),
+            TimerMode::Once,
+        ));
+        match request.position {
+            Some(position) => commands.spawn((
+                AudioPlayer(handle.clone()),
+                playback
+                    .with_spatial(true)
+                    .with_spatial_scale(SpatialScale::new(SPATIAL_SCALE)),
+                Transform::from_translation(position),
+                lifetime,
+            )),
+            None => commands.spawn((AudioPlayer(handle.clone()), playback, lifetime)),
+        };
+    }
+}
+
+fn expire_sfx(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut sfx_query: Query<(Entity, &mut SfxLifetime)>,
+) {
+    for (entity, mut lifetime) in &mut sfx_query {
+        if lifetime.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Leans towards the combat layer the more enemies are close to the player.
+fn crossfade_music(
+    time: Res<Time>,
+    settings: Res<AudioSettings>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    enemy_query: Query<&GlobalTransform, (With<Enemy>, Without<Dead>)>,
+    mut combat_mix: Local<f32>,
+    mut music_query: Query<(&MusicLayer, &mut AudioSink)>,
+) {
+    let nearby = player_query.single().map_or(0, |player| {
+        enemy_query
+            .iter()
+            .filter(|enemy| enemy.translation().distance(player.translation()) < COMBAT_RANGE)
+            .count()
+    });
+    let target = (nearby as f32 / FULL_COMBAT_ENEMIES).min(1.0);
+    let step = time.delta_secs() / CROSSFADE_SECONDS;
+    *combat_mix += (target - *combat_mix).clamp(-step, step);
+
+    for (layer, mut sink) in &mut music_query {
+        let mix = match layer {
+            MusicLayer::Exploration => 1.0 - *combat_mix,
+            MusicLayer::Combat => *combat_mix,
+        };
+        // Sinks don't follow GlobalVolume once playing, so master goes in here too
+        sink.set_volume(Volume::Linear(mix * settings.music * settings.master));
+    }
+}
+
+fn apply_master_volume(settings: Res<AudioSettings>, mut global: ResMut<GlobalVolume>) {
+    if settings.is_changed() {
+        global.volume = Volume::Linear(settings.master);
+    }
+}
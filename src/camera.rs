@@ -0,0 +1,293 @@
+use avian3d::prelude::*;
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+    transform::TransformSystem,
+};
+
+use crate::GameState;
+use crate::gameplay::enemies::melee_creep::{Dead, Enemy};
+use crate::player::Player;
+
+/// Enemies further away than this can't be locked on to
+const LOCK_ON_RANGE: f32 = 30.0;
+/// Radius of the sphere swept from the player to the camera
+const CAMERA_RADIUS: f32 = 0.2;
+/// Trauma lost per second
+const SHAKE_DECAY: f32 = 1.5;
+/// Offset and roll at full trauma
+const SHAKE_MAX_OFFSET: f32 = 0.3;
+const SHAKE_MAX_ROLL: f32 = 0.05;
+
+pub struct ThirdPersonCameraPlugin;
+
+impl Plugin for ThirdPersonCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<(ThirdPersonCamera, CameraSettings)>()
+            .init_resource::<CameraSettings>()
+            .add_systems(
+                Update,
+                (camera_input, update_lock_on)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                PostUpdate,
+                update_camera_transform
+                    .after(PhysicsSet::Sync)
+                    .before(TransformSystem::TransformPropagate)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CameraSettings {
+    /// Radians per pixel of mouse movement
+    pub sensitivity: Vec2,
+    /// Degrees per second with the right stick all the way over
+    pub stick_speed: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+    /// Metres per scroll wheel line
+    pub zoom_speed: f32,
+    /// Higher is snappier
+    pub follow_smoothing: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: Vec2::splat(0.005),
+            stick_speed: 200.0,
+            invert_x: false,
+            invert_y: false,
+            zoom_speed: 1.0,
+            follow_smoothing: 10.0,
+        }
+    }
+}
+
+/// Spring-arm camera orbiting the player.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[require(Camera3d)]
+pub struct ThirdPersonCamera {
+    pub yaw: f32,
+    /// Positive looks down on the player
+    pub pitch: f32,
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+    /// Arm length the player asked for with the scroll wheel
+    pub zoom: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// Camera-space offset, x puts the player off to one side
+    pub shoulder_offset: Vec3,
+    /// Height above the player's origin the camera looks at
+    pub focus_height: f32,
+    /// Smoothed point the arm is attached to
+    pub focus: Vec3,
+    /// Actual arm length after pulling in for walls
+    pub arm_length: f32,
+    pub lock_on: Option<Entity>,
+    /// 0 to 1, shakes with its square and wears off by itself
+    pub trauma: f32,
+}
+
+impl Default for ThirdPersonCamera {
+    fn default() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.3,
+            min_pitch: -0.25,
+            max_pitch: 1.5,
+            zoom: 6.0,
+            min_zoom: 2.0,
+            max_zoom: 12.0,
+            shoulder_offset: Vec3::new(0.75, 0.25, 0.0),
+            focus_height: 1.0,
+            focus: Vec3::ZERO,
+            arm_length: 6.0,
+            lock_on: None,
+            trauma: 0.0,
+        }
+    }
+}
+
+impl ThirdPersonCamera {
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, -self.pitch, 0.0)
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+}
+
+fn camera_input(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    gamepads: Query<&Gamepad>,
+    mut camera_query: Query<&mut ThirdPersonCamera>,
+) {
+    // Radians to turn by this frame
+    let mut delta = Vec2::ZERO;
+    for event in mouse_motion_events.read() {
+        delta += event.delta * settings.sensitivity;
+    }
+    for gamepad in &gamepads {
+        let stick = gamepad.right_stick();
+        delta +=
+            Vec2::new(stick.x, -stick.y) * settings.stick_speed.to_radians() * time.delta_secs();
+    }
+    let mut scroll = 0.0;
+    for event in mouse_wheel_events.read() {
+        scroll += event.y;
+    }
+    if settings.invert_x {
+        delta.x = -delta.x;
+    }
+    if settings.invert_y {
+        delta.y = -delta.y;
+    }
+
+    for mut cam in &mut camera_query {
+        // Lock-on owns the yaw, the player can still tilt
+        if cam.lock_on.is_none() {
+            cam.yaw -= delta.x;
+        }
+        cam.pitch = (cam.pitch + delta.y).clamp(cam.min_pitch, cam.max_pitch);
+        cam.zoom = (cam.zoom - scroll * settings.zoom_speed).clamp(cam.min_zoom, cam.max_zoom);
+    }
+}
+
+fn update_lock_on(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut camera_query: Query<&mut ThirdPersonCamera>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    enemy_query: Query<(Entity, &GlobalTransform), (With<Enemy>, Without<Dead>)>,
+) {
+    let Ok(player_transform) = player_query.single() else {
+        return;
+    };
+    let player_pos = player_transform.translation();
+    let toggle = keyboard.just_pressed(KeyCode::Tab) || mouse.just_pressed(MouseButton::Middle);
+
+    for mut cam in &mut camera_query {
+        if toggle {
+            cam.lock_on = match cam.lock_on {
+                Some(_) => None,
+                None => enemy_query
+                    .iter()
+                    .map(|(entity, transform)| {
+                        (entity, transform.translation().distance(player_pos))
+                    })
+                    .filter(|(_, distance)| *distance < LOCK_ON_RANGE)
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map(|(entity, _)| entity),
+            };
+        }
+
+        // Drop the target once it dies or gets away
+        let Some(target) = cam.lock_on else {
+            continue;
+        };
+        match enemy_query.get(target) {
+            Ok((_, transform)) if transform.translation().distance(player_pos) < LOCK_ON_RANGE => {}
+            _ => cam.lock_on = None,
+        }
+    }
+}
+
+fn update_camera_transform(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    spatial_query: SpatialQuery,
+    collider_query: Query<(Has<Sensor>, Option<&ColliderOf>), With<Collider>>,
+    body_query: Query<&RigidBody>,
+    player_query: Query<(Entity, &GlobalTransform), With<Player>>,
+    enemy_query: Query<&GlobalTransform, (With<Enemy>, Without<Dead>)>,
+    mut camera_query: Query<(&mut ThirdPersonCamera, &mut Transform)>,
+) {
+    let Ok((player_entity, player_transform)) = player_query.single() else {
+        return;
+    };
+    let dt = time.delta_secs();
+    let smoothing = 1.0 - (-settings.follow_smoothing * dt).exp();
+
+    for (mut cam, mut transform) in &mut camera_query {
+        let mut focus_target = player_transform.translation() + Vec3::Y * cam.focus_height;
+
+        if let Some(enemy_transform) = cam.lock_on.and_then(|e| enemy_query.get(e).ok()) {
+            // Turn to keep the enemy in front and look between the two of them
+            let to_enemy = enemy_transform.translation() - player_transform.translation();
+            let flat = Vec2::new(to_enemy.x, to_enemy.z);
+            if flat.length_squared() > 0.01 {
+                let target_yaw = (-flat.x).atan2(-flat.y);
+                let diff = (target_yaw - cam.yaw + std::f32::consts::PI)
+                    .rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                cam.yaw += diff * smoothing;
+            }
+            focus_target += to_enemy * 0.3;
+        }
+
+        cam.focus = cam.focus.lerp(focus_target, smoothing);
+
+        let rotation = cam.rotation();
+        let arm = rotation * (cam.shoulder_offset + Vec3::Z * cam.zoom);
+        let max_length = arm.length();
+        let Ok(direction) = Dir3::new(arm) else {
+            continue;
+        };
+
+        // Pull in instantly when level geometry is in the way, ease back out. Only
+        // static colliders count, enemies and fireballs passing by shouldn't pop it.
+        let is_level = |entity: Entity| {
+            collider_query
+                .get(entity)
+                .is_ok_and(|(sensor, collider_of)| {
+                    let body = collider_of.map_or(entity, |of| of.body);
+                    !sensor && body_query.get(body).map_or(true, |body| body.is_static())
+                })
+        };
+        let filter = SpatialQueryFilter::from_excluded_entities([player_entity]);
+        let hit_distance = spatial_query
+            .cast_shape_predicate(
+                &Collider::sphere(CAMERA_RADIUS),
+                cam.focus,
+                Quat::IDENTITY,
+                direction,
+                &ShapeCastConfig::from_max_distance(max_length),
+                &filter,
+                &is_level,
+            )
+            .map(|hit| hit.distance)
+            .unwrap_or(max_length);
+        cam.arm_length = if hit_distance < cam.arm_length {
+            hit_distance
+        } else {
+            cam.arm_length + (hit_distance - cam.arm_length) * smoothing
+        };
+
+        transform.translation = cam.focus + *direction * cam.arm_length;
+        transform.rotation = rotation;
+
+        // Layered sines stand in for noise, different rates per axis so it doesn't loop visibly
+        if cam.trauma > 0.0 {
+            let shake = cam.trauma * cam.trauma;
+            let t = time.elapsed_secs() * 30.0;
+            let offset =
+                Vec3::new((t * 1.1).sin(), (t * 1.7 + 1.0).sin(), 0.0) * shake * SHAKE_MAX_OFFSET;
+            transform.translation += rotation * offset;
+            transform.rotation *=
+                Quat::from_rotation_z((t * 0.9 + 2.0).sin() * shake * SHAKE_MAX_ROLL);
+            cam.trauma = (cam.trauma - SHAKE_DECAY * dt).max(0.0);
+        }
+    }
+}
@@ -5,13 +5,24 @@ use bevy_inspector_egui::{bevy_egui::EguiPlugin, quick::WorldInspectorPlugin};
 pub struct DevUtilsPlugin;
 
 // use crate::player::Health;
+use crate::gameplay::moving_platforms::{
+    MovingPlatform, PlatformMode, PlatformPath, PlatformValidation, PlatformWaypoint,
+};
+use crate::gameplay::traversal::TraversalAbilities;
 use crate::player::Player;
 
 impl Plugin for DevUtilsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(InspectorActive(false));
-        app.add_plugins(PhysicsDebugPlugin::default())
-            .add_systems(Update, draw_player_gizmo);
+        app.add_plugins(PhysicsDebugPlugin::default()).add_systems(
+            Update,
+            (
+                draw_player_gizmo,
+                draw_platform_paths,
+                label_waypoints,
+                unlock_all_traversal,
+            ),
+        );
         app.add_plugins((
             EguiPlugin {
                 enable_multipass_for_primary_context: true,
@@ -38,6 +49,89 @@ fn draw_player_gizmo(mut gizmos: Gizmos, query: Query<&Transform, With<Player>>)
     }
 }
 
+/// F2 hands out every movement ability so levels can be tested without progressing
+fn unlock_all_traversal(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut query: Query<&mut TraversalAbilities, With<Player>>,
+) {
+    if keyboard.just_pressed(KeyCode::F2) {
+        for mut abilities in &mut query {
+            *abilities = TraversalAbilities::all();
+        }
+    }
+}
+
+fn draw_platform_paths(
+    mut gizmos: Gizmos,
+    validation: Res<PlatformValidation>,
+    query: Query<(&MovingPlatform, &PlatformPath)>,
+) {
+    // Red paths mean something in the level needs fixing, check the log
+    let color = if validation.errors.is_empty() {
+        Color::from(css::YELLOW)
+    } else {
+        Color::from(css::RED)
+    };
+
+    for (platform, path) in &query {
+        let len = path.locations.len();
+        let looping = platform.mode == PlatformMode::Loop;
+        let legs = if looping { len } else { len.saturating_sub(1) };
+        for from in 0..legs {
+            let to = (from + 1) % len;
+            let points =
+                (0..=16).map(|i| path.sample(platform.curve, looping, from, to, i as f32 / 16.0));
+            gizmos.linestrip(points, color);
+        }
+        for (location, rotation) in path.locations.iter().zip(&path.rotations) {
+            gizmos.sphere(Isometry3d::new(*location, *rotation), 0.25, color);
+        }
+    }
+}
+
+/// Screen-space label showing a waypoint's index
+#[derive(Component)]
+struct WaypointLabel(Entity);
+
+fn label_waypoints(
+    mut commands: Commands,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    waypoint_query: Query<(Entity, &PlatformWaypoint, &GlobalTransform)>,
+    mut label_query: Query<(Entity, &WaypointLabel, &mut Node, &mut Visibility)>,
+    new_waypoints: Query<(Entity, &PlatformWaypoint), Added<PlatformWaypoint>>,
+) {
+    for (entity, waypoint) in &new_waypoints {
+        commands.spawn((
+            Text::new(waypoint.index.to_string()),
+            TextColor(Color::from(css::YELLOW)),
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            WaypointLabel(entity),
+        ));
+    }
+
+    let Ok((camera, camera_transform)) = camera_query.single() else {
+        return;
+    };
+    for (label_entity, label, mut node, mut visibility) in &mut label_query {
+        let Ok((_, _, transform)) = waypoint_query.get(label.0) else {
+            commands.entity(label_entity).despawn();
+            continue;
+        };
+        match camera.world_to_viewport(camera_transform, transform.translation()) {
+            Ok(position) => {
+                node.left = Val::Px(position.x);
+                node.top = Val::Px(position.y);
+                *visibility = Visibility::Visible;
+            }
+            // Behind the camera
+            Err(_) => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
 // uncomment this and the health import to sanity check the player health/ui
 // fn damage_player(mut health_query: Query<&mut Health, With<Player>>) {
 //     if let Ok(mut health) = health_query.single_mut() {
@@ -0,0 +1,145 @@
+use avian3d::prelude::*;
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::GameState;
+use crate::camera::ThirdPersonCamera;
+use crate::gameplay::enemies::melee_creep::{Dead, Enemy};
+use crate::player::Player;
+
+/// How far the crosshair ray reaches when it doesn't hit anything
+const MAX_AIM_DISTANCE: f32 = 200.0;
+
+pub struct AimPlugin;
+
+impl Plugin for AimPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AimSettings>()
+            .init_resource::<AimSettings>()
+            .init_resource::<AimTarget>()
+            .add_systems(
+                PostUpdate,
+                // The camera has to be in place first or the ray lags a frame behind
+                update_aim_target
+                    .after(TransformSystem::TransformPropagate)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Where projectiles leave the player, spawned as a child of the player.
+#[derive(Component)]
+pub struct CastPoint;
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct AimSettings {
+    /// Half-angle in degrees of the cone enemies get pulled into
+    pub assist_angle: f32,
+    /// 0 is no help, 1 snaps straight onto the enemy
+    pub assist_strength: f32,
+    pub assist_range: f32,
+}
+
+impl Default for AimSettings {
+    fn default() -> Self {
+        Self {
+            assist_angle: 6.0,
+            assist_strength: 0.6,
+            assist_range: 40.0,
+        }
+    }
+}
+
+/// What the crosshair is over this frame.
+#[derive(Resource, Default)]
+pub struct AimTarget {
+    pub point: Vec3,
+    pub entity: Option<Entity>,
+    /// Set when the aim was bent toward an enemy by aim assist
+    pub assisted: bool,
+}
+
+impl AimTarget {
+    /// Direction to fire from `origin` so the shot lands on the crosshair.
+    pub fn direction_from(&self, origin: Vec3, fallback: Dir3) -> Dir3 {
+        let direction = self.point - origin;
+        // Target's practically on top of us, don't fire sideways
+        if direction.length_squared() < 1.0 {
+            return fallback;
+        }
+        Dir3::new(direction).unwrap_or(fallback)
+    }
+}
+
+fn update_aim_target(
+    settings: Res<AimSettings>,
+    mut aim: ResMut<AimTarget>,
+    spatial_query: SpatialQuery,
+    gamepads: Query<&Gamepad>,
+    sensor_query: Query<(), With<Sensor>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<ThirdPersonCamera>>,
+    player_query: Query<Entity, With<Player>>,
+    enemy_query: Query<&GlobalTransform, (With<Enemy>, Without<Dead>)>,
+) {
+    let Ok((camera, camera_transform)) = camera_query.single() else {
+        return;
+    };
+    let Some(centre) = camera.logical_viewport_size().map(|size| size / 2.0) else {
+        return;
+    };
+    let Ok(ray) = camera.viewport_to_world(camera_transform, centre) else {
+        return;
+    };
+
+    let filter = SpatialQueryFilter::from_excluded_entities(player_query.iter());
+    let cast = |direction: Dir3| {
+        spatial_query.cast_ray_predicate(
+            ray.origin,
+            direction,
+            MAX_AIM_DISTANCE,
+            true,
+            &filter,
+            &|entity| !sensor_query.contains(entity),
+        )
+    };
+
+    // Aim assist is only for sticks, mice are accurate enough
+    let mut direction = ray.direction;
+    aim.assisted = false;
+    if !gamepads.is_empty() {
+        let assist_cos = settings.assist_angle.to_radians().cos();
+        let closest = enemy_query
+            .iter()
+            .filter_map(|transform| {
+                let to_enemy = transform.translation() - ray.origin;
+                let distance = to_enemy.length();
+                let cos = to_enemy.dot(*ray.direction) / distance;
+                (distance < settings.assist_range && cos > assist_cos).then_some((to_enemy, cos))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        if let Some((to_enemy, _)) = closest {
+            let assisted = ray.direction.slerp(
+                Dir3::new(to_enemy).unwrap_or(ray.direction),
+                settings.assist_strength,
+            );
+            // Don't pull the shot onto something behind a wall
+            let blocked = cast(assisted).is_some_and(|hit| hit.distance < to_enemy.length() - 1.0);
+            if !blocked {
+                direction = assisted;
+                aim.assisted = true;
+            }
+        }
+    }
+
+    match cast(direction) {
+        Some(hit) => {
+            aim.point = ray.origin + *direction * hit.distance;
+            aim.entity = Some(hit.entity);
+        }
+        None => {
+            aim.point = ray.origin + *direction * MAX_AIM_DISTANCE;
+            aim.entity = None;
+        }
+    }
+}
@@ -1,14 +1,18 @@
+use std::collections::HashSet;
+
 use avian3d::prelude::*;
-use bevy::{color::palettes::css, prelude::*};
+use bevy::prelude::*;
 
 use crate::GameState;
-use crate::gameplay::enemies::melee_creep::Enemy;
+use crate::gameplay::elements::{Element, ElementalHit};
+use crate::gameplay::water::WaterVolume;
+use crate::player::Player;
 
 pub struct FireballPlugin;
 
 impl Plugin for FireballPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.add_event::<FireballImpact>().add_systems(
             Update,
             fireball_collision_system.run_if(in_state(GameState::InGame)),
         );
@@ -18,6 +22,14 @@ impl Plugin for FireballPlugin {
 #[derive(Component)]
 pub struct Fireball {
     pub damage: f32,
+    pub element: Element,
+}
+
+/// A fireball hit something and burst.
+#[derive(Event)]
+pub struct FireballImpact {
+    pub position: Vec3,
+    pub element: Element,
 }
 
 pub fn spawn_fireball(
@@ -28,15 +40,21 @@ pub fn spawn_fireball(
     direction: Dir3,
     damage: f32,
     speed: f32,
+    element: Element,
 ) {
     commands.spawn((
         Mesh3d(meshes.add(Sphere { radius: 0.3 })),
-        MeshMaterial3d(materials.add(Color::from(css::DARK_ORANGE))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: element.color(),
+            // Glows a bit so it reads as fire rather than a ball
+            emissive: element.color().to_linear() * 4.0,
+            ..default()
+        })),
         Transform::from_translation(spawn_pos),
         RigidBody::Dynamic,
         Collider::sphere(0.3),
         LinearVelocity(direction * speed),
-        Fireball { damage },
+        Fireball { damage, element },
         CollisionEventsEnabled,
     ));
 }
@@ -44,24 +62,40 @@ pub fn spawn_fireball(
 fn fireball_collision_system(
     mut commands: Commands,
     mut collision_events: EventReader<CollisionStarted>,
-    mut enemy_query: Query<(&mut Enemy, Entity)>,
-    fireball_query: Query<&Fireball>,
+    mut hits: EventWriter<ElementalHit>,
+    mut impacts: EventWriter<FireballImpact>,
+    fireball_query: Query<(&Fireball, &Transform)>,
+    ignored_query: Query<(), Or<(With<Player>, With<Fireball>)>>,
+    sensor_query: Query<(), (With<Sensor>, Without<WaterVolume>)>,
 ) {
+    let mut spent = HashSet::new();
     for CollisionStarted(e1, e2) in collision_events.read() {
-        let (fireball_entity, enemy_entity) =
-            if fireball_query.get(*e1).is_ok() && enemy_query.get(*e2).is_ok() {
-                (*e1, *e2)
-            } else if fireball_query.get(*e2).is_ok() && enemy_query.get(*e1).is_ok() {
-                (*e2, *e1)
-            } else {
-                continue;
-            };
-
-        let fireball = fireball_query.get(fireball_entity).unwrap();
-        if let Ok((mut enemy, enemy_entity)) = enemy_query.get_mut(enemy_entity) {
-            enemy.health -= fireball.damage;
-            println!("Enemy hit! Health: {}", enemy.health);
-            commands.entity(fireball_entity).despawn();
+        let (fireball_entity, other) = if fireball_query.contains(*e1) {
+            (*e1, *e2)
+        } else if fireball_query.contains(*e2) {
+            (*e2, *e1)
+        } else {
+            continue;
+        };
+        // Flies through triggers and pickups, but water counts so ice can freeze it
+        if ignored_query.contains(other) || sensor_query.contains(other) {
+            continue;
+        }
+        // Might touch two things in the same frame, only the first one counts
+        if !spent.insert(fireball_entity) {
+            continue;
         }
+
+        let (fireball, transform) = fireball_query.get(fireball_entity).unwrap();
+        hits.write(ElementalHit {
+            target: other,
+            element: fireball.element,
+            damage: fireball.damage,
+        });
+        impacts.write(FireballImpact {
+            position: transform.translation,
+            element: fireball.element,
+        });
+        commands.entity(fireball_entity).despawn();
     }
 }
@@ -1 +1,2 @@
+pub mod aim;
 pub mod fireball;
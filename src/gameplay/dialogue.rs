@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::gameplay::interaction::{Interact, Interactable, InteractionSet};
+use crate::gameplay::items::{DropItems, Inventory, ItemDefinition, ItemLibrary};
+use crate::gameplay::quests::{QuestLog, StartQuest};
+use crate::library::{FolderLibrary, LibraryPlugin, RonAsset};
+use crate::player::Player;
+
+/// How close the player needs to be to start talking
+const TALK_RANGE: f32 = 3.0;
+/// Roughly head height, where the prompt floats
+const NPC_HEIGHT: f32 = 2.3;
+/// Redirects that loop back on themselves give up after this many jumps
+const MAX_REDIRECTS: usize = 16;
+
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(LibraryPlugin::<DialogueDefinition>::new("dialogue"))
+            .register_type::<Npc>()
+            .init_resource::<ActiveDialogue>()
+            .add_event::<ChooseDialogueOption>()
+            .add_systems(
+                Update,
+                (name_npc_prompts, talk_to_npcs, dialogue_input, run_dialogue)
+                    .chain()
+                    // After interaction, so the key that ends a conversation can't
+                    // also start it again
+                    .after(InteractionSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Friendly character the player can walk up to and talk to. Authored in Blender.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Interactable = Interactable::new("Talk", TALK_RANGE).with_height(NPC_HEIGHT))]
+pub struct Npc {
+    pub name: String,
+    /// Dialogue id, the file name in `assets/dialogue` without the extension
+    pub dialogue: String,
+}
+
+/// Read from `assets/dialogue/<id>.dialogue.ron`.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct DialogueDefinition {
+    /// Node the conversation opens on
+    pub start: String,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+impl RonAsset for DialogueDefinition {
+    const EXTENSION: &'static str = "dialogue.ron";
+}
+
+/// Dialogue trees by id, see `Npc::dialogue`.
+pub type DialogueLibrary = FolderLibrary<DialogueDefinition>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DialogueNode {
+    /// Defaults to the NPC's name
+    #[serde(default)]
+    pub speaker: Option<String>,
+    pub text: String,
+    /// Checked on the way in, the first one that holds is jumped to instead of
+    /// showing this node. Handy for greeting the player differently per quest state.
+    #[serde(default)]
+    pub redirects: Vec<Redirect>,
+    /// Run when the node is shown
+    #[serde(default)]
+    pub actions: Vec<DialogueAction>,
+    /// Ones whose conditions fail are left out. With none, the player just
+    /// continues to `next`.
+    #[serde(default)]
+    pub choices: Vec<DialogueChoice>,
+    /// Where "continue" goes, `None` ends the conversation
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Redirect {
+    pub conditions: Vec<Condition>,
+    pub to: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DialogueChoice {
+    pub text: String,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    #[serde(default)]
+    pub actions: Vec<DialogueAction>,
+    /// `None` ends the conversation
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Condition {
+    QuestNotStarted(String),
+    QuestActive(String),
+    QuestCompleted(String),
+    HasItem { item: String, count: u32 },
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn holds(&self, log: &QuestLog, inventory: &Inventory) -> bool {
+        match self {
+            Condition::QuestNotStarted(id) => !log.is_active(id) && !log.is_completed(id),
+            Condition::QuestActive(id) => log.is_active(id),
+            Condition::QuestCompleted(id) => log.is_completed(id),
+            Condition::HasItem { item, count } => inventory.count(item) >= *count,
+            Condition::Not(condition) => !condition.holds(log, inventory),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum DialogueAction {
+    GiveItem { item: String, count: u32 },
+    TakeItem { item: String, count: u32 },
+    StartQuest(String),
+}
+
+/// The conversation on screen, if any. Player controls are off while it's set.
+#[derive(Resource, Default)]
+pub struct ActiveDialogue(pub Option<Conversation>);
+
+pub struct Conversation {
+    pub npc: Entity,
+    pub dialogue: String,
+    pub node: String,
+    /// Indices into the node's choices that passed their conditions
+    pub choices: Vec<usize>,
+    /// Highlighted choice, for picking with a gamepad
+    pub selected: usize,
+    /// Set once the node's redirects and actions have run
+    entered: bool,
+}
+
+/// Pick one of the shown choices, counting from 0. With no choices any
+/// index just continues.
+#[derive(Event)]
+pub struct ChooseDialogueOption(pub usize);
+
+/// Fills in the prompt now that the name is known.
+fn name_npc_prompts(mut npc_query: Query<(&Npc, &mut Interactable), Added<Npc>>) {
+    for (npc, mut interactable) in &mut npc_query {
+        interactable.prompt = format!("Talk to {}", npc.name);
+    }
+}
+
+fn talk_to_npcs(
+    mut interactions: EventReader<Interact>,
+    mut active: ResMut<ActiveDialogue>,
+    npc_query: Query<&Npc>,
+) {
+    for Interact(entity) in interactions.read() {
+        let Ok(npc) = npc_query.get(*entity) else {
+            continue;
+        };
+        active.0 = Some(Conversation {
+            npc: *entity,
+            dialogue: npc.dialogue.clone(),
+            node: String::new(),
+            choices: Vec::new(),
+            selected: 0,
+            entered: false,
+        });
+    }
+}
+
+/// Number keys or the interact key pick a choice, Escape walks away.
+fn dialogue_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut active: ResMut<ActiveDialogue>,
+    mut choose: EventWriter<ChooseDialogueOption>,
+) {
+    let Some(conversation) = active.0.as_ref() else {
+        return;
+    };
+    // Whatever opened it this frame shouldn't also skip the first line
+    if !conversation.entered {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Escape) {
+        active.0 = None;
+        return;
+    }
+
+    let gamepad = gamepads.iter().next();
+    let pad_just_pressed = |button| gamepad.is_some_and(|pad| pad.just_pressed(button));
+    let count = conversation.choices.len().max(1);
+    let mut selected = conversation.selected;
+    if keyboard.just_pressed(KeyCode::ArrowDown) || pad_just_pressed(GamepadButton::DPadDown) {
+        selected = (selected + 1) % count;
+    }
+    if keyboard.just_pressed(KeyCode::ArrowUp) || pad_just_pressed(GamepadButton::DPadUp) {
+        selected = (selected + count - 1) % count;
+    }
+    if keyboard.just_pressed(KeyCode::KeyE) || pad_just_pressed(GamepadButton::West) {
+        choose.write(ChooseDialogueOption(selected));
+    }
+
+    const NUMBER_KEYS: [KeyCode; 9] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    for (index, key) in NUMBER_KEYS.iter().enumerate().take(count) {
+        if keyboard.just_pressed(*key) {
+            choose.write(ChooseDialogueOption(index));
+        }
+    }
+    // Only touch it when it moves, the dialogue box redraws on every change
+    if selected != conversation.selected
+        && let Some(conversation) = active.0.as_mut()
+    {
+        conversation.selected = selected;
+    }
+}
+
+/// Moves the conversation along: follows the picked choice, then runs the new
+/// node's redirects and actions.
+fn run_dialogue(
+    mut choose: EventReader<ChooseDialogueOption>,
+    mut active: ResMut<ActiveDialogue>,
+    library: Res<DialogueLibrary>,
+    definitions: Res<Assets<DialogueDefinition>>,
+    item_library: Res<ItemLibrary>,
+    item_definitions: Res<Assets<ItemDefinition>>,
+    log: Res<QuestLog>,
+    mut start_quest: EventWriter<StartQuest>,
+    mut drops: EventWriter<DropItems>,
+    mut player_query: Query<(&mut Inventory, &GlobalTransform), With<Player>>,
+) {
+    let picked = choose.read().last().map(|choice| choice.0);
+    // Nothing to do between lines, and looking mutably would redraw the box
+    if picked.is_none()
+        && active
+            .0
+            .as_ref()
+            .is_none_or(|conversation| conversation.entered)
+    {
+        return;
+    }
+    let Some(conversation) = active.0.as_mut() else {
+        return;
+    };
+    let Some(definition) = library.get(&definitions, &conversation.dialogue) else {
+        warn!("No dialogue called {}", conversation.dialogue);
+        active.0 = None;
+        return;
+    };
+    let Ok((mut inventory, transform)) = player_query.single_mut() else {
+        return;
+    };
+    let mut run = |actions: &[DialogueAction], inventory: &mut Inventory| {
+        for action in actions {
+            match action {
+                DialogueAction::GiveItem { item, count } => {
+                    let Some(item_definition) = item_library.get(&item_definitions, item) else {
+                        warn!("Dialogue gives unknown item {item}");
+                        continue;
+                    };
+                    // Gifts that don't fit end up at the player's feet
+                    let left = inventory.add(item, item_definition, *count);
+                    if left > 0 {
+                        drops.write(DropItems {
+                            item: item.clone(),
+                            count: left,
+                            position: transform.translation() + Vec3::Y * 0.5,
+                        });
+                    }
+                }
+                DialogueAction::TakeItem { item, count } => {
+                    if !inventory.remove(item, *count) {
+                        warn!("Dialogue wanted {count} {item} but the player has fewer");
+                    }
+                }
+                DialogueAction::StartQuest(id) => {
+                    start_quest.write(StartQuest(id.clone()));
+                }
+            }
+        }
+    };
+
+    let next = if !conversation.entered {
+        Some(definition.start.clone())
+    } else if let Some(index) = picked {
+        let node = definition.nodes.get(&conversation.node);
+        match node.and_then(|node| {
+            conversation
+                .choices
+                .get(index)
+                .and_then(|choice| node.choices.get(*choice))
+        }) {
+            Some(choice) => {
+                run(&choice.actions, &mut inventory);
+                choice.next.clone()
+            }
+            // Nothing to pick, so it's a "continue"
+            None if conversation.choices.is_empty() => node.and_then(|node| node.next.clone()),
+            None => return,
+        }
+    } else {
+        return;
+    };
+
+    let Some(mut next) = next else {
+        active.0 = None;
+        return;
+    };
+    for _ in 0..MAX_REDIRECTS {
+        let Some(node) = definition.nodes.get(&next) else {
+            break;
+        };
+        match node.redirects.iter().find(|redirect| {
+            redirect
+                .conditions
+                .iter()
+                .all(|condition| condition.holds(&log, &inventory))
+        }) {
+            Some(redirect) => next = redirect.to.clone(),
+            None => break,
+        }
+    }
+    let Some(node) = definition.nodes.get(&next) else {
+        warn!("Dialogue {} has no node {next}", conversation.dialogue);
+        active.0 = None;
+        return;
+    };
+
+    run(&node.actions, &mut inventory);
+    conversation.choices = node
+        .choices
+        .iter()
+        .enumerate()
+        .filter(|(_, choice)| {
+            choice
+                .conditions
+                .iter()
+                .all(|condition| condition.holds(&log, &inventory))
+        })
+        .map(|(index, _)| index)
+        .collect();
+    conversation.node = next;
+    conversation.selected = 0;
+    conversation.entered = true;
+}
@@ -0,0 +1,355 @@
+use std::collections::HashSet;
+
+use avian3d::prelude::*;
+use bevy::{color::palettes::css, prelude::*};
+
+use crate::GameState;
+use crate::gameplay::enemies::melee_creep::{Dead, Enemy};
+use crate::gameplay::stats::{Stat, Stats};
+use crate::gameplay::status_effects::{
+    ApplyStatusExt, Burning, Poisoned, Slowed, StatusEffectSet, Wet,
+};
+use crate::gameplay::water::{Swimming, WaterVolume};
+use crate::player::{Health, Player};
+
+/// Burning left on whatever fire hits
+const BURN_DAMAGE_PER_SECOND: f32 = 4.0;
+const BURN_DURATION: f32 = 3.0;
+/// How far lightning jumps between wet enemies
+const CHAIN_RADIUS: f32 = 8.0;
+/// Each jump does this much of the previous one's damage
+const CHAIN_FALLOFF: f32 = 0.7;
+/// Seconds before frozen water melts again
+const ICE_DURATION: f32 = 12.0;
+const ICE_THICKNESS: f32 = 0.3;
+
+pub struct ElementPlugin;
+
+impl Plugin for ElementPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<(Resistances, Flammable)>()
+            .add_event::<DealDamage>()
+            .add_event::<ElementalHit>()
+            .add_event::<DamageDealt>()
+            .add_systems(
+                Update,
+                (
+                    soak_swimmers,
+                    elemental_hits,
+                    burn_flammables,
+                    melt_ice,
+                    apply_damage,
+                )
+                    .chain()
+                    .in_set(DamageSet)
+                    .after(StatusEffectSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// `DealDamage` has landed once this set is done.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DamageSet;
+
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Element {
+    #[default]
+    Physical,
+    Fire,
+    Ice,
+    Lightning,
+    Poison,
+}
+
+impl Element {
+    pub fn color(self) -> Color {
+        Color::from(match self {
+            Element::Physical => css::LIGHT_GRAY,
+            Element::Fire => css::DARK_ORANGE,
+            Element::Ice => css::LIGHT_CYAN,
+            Element::Lightning => css::YELLOW,
+            Element::Poison => css::LIMEGREEN,
+        })
+    }
+}
+
+/// Fraction of each element's damage shrugged off. 1 is immune, negative is a weakness.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component, Default)]
+pub struct Resistances {
+    pub physical: f32,
+    pub fire: f32,
+    pub ice: f32,
+    pub lightning: f32,
+    pub poison: f32,
+}
+
+impl Resistances {
+    pub fn multiplier(&self, element: Element) -> f32 {
+        let resistance = match element {
+            Element::Physical => self.physical,
+            Element::Fire => self.fire,
+            Element::Ice => self.ice,
+            Element::Lightning => self.lightning,
+            Element::Poison => self.poison,
+        };
+        (1.0 - resistance).max(0.0)
+    }
+}
+
+/// Everything that hurts goes through this so resistances and armor always apply.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DealDamage {
+    pub target: Entity,
+    pub amount: f32,
+    pub element: Element,
+}
+
+/// Damage that actually landed, after resistances. What hit feedback reacts to.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DamageDealt {
+    pub target: Entity,
+    pub amount: f32,
+    pub element: Element,
+    /// Hit a weakness
+    pub crit: bool,
+}
+
+/// A spell of some element landed on something, whatever it is.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ElementalHit {
+    pub target: Entity,
+    pub element: Element,
+    pub damage: f32,
+}
+
+/// Prop that catches fire, burns for a while spreading to whatever's close, then is gone.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+pub struct Flammable {
+    /// Seconds it burns for once lit
+    pub fuel: f32,
+    /// How far the flames reach
+    pub radius: f32,
+}
+
+impl Default for Flammable {
+    fn default() -> Self {
+        Self {
+            fuel: 6.0,
+            radius: 3.0,
+        }
+    }
+}
+
+/// Walkable ice over a frozen water volume.
+#[derive(Component)]
+pub struct IceSheet {
+    pub water: Entity,
+    pub remaining: f32,
+}
+
+/// Anything swimming comes out wet and stays that way for a bit.
+fn soak_swimmers(mut commands: Commands, query: Query<Entity, With<Swimming>>) {
+    for entity in &query {
+        commands.entity(entity).apply_status(Wet::default());
+    }
+}
+
+fn elemental_hits(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut hits: EventReader<ElementalHit>,
+    mut damage: EventWriter<DealDamage>,
+    enemy_query: Query<(Entity, &GlobalTransform, Has<Wet>), (With<Enemy>, Without<Dead>)>,
+    flammable_query: Query<(), With<Flammable>>,
+    water_query: Query<&ColliderAabb, With<WaterVolume>>,
+    mut ice_query: Query<&mut IceSheet>,
+) {
+    for hit in hits.read() {
+        let ElementalHit {
+            target,
+            element,
+            damage: amount,
+        } = *hit;
+
+        if let Ok((_, transform, wet)) = enemy_query.get(target) {
+            damage.write(DealDamage {
+                target,
+                amount,
+                element,
+            });
+            let mut enemy = commands.entity(target);
+            match element {
+                // Wet things don't catch, the fire just dries them off
+                Element::Fire if wet => {
+                    enemy.remove::<Wet>();
+                }
+                Element::Fire => {
+                    enemy.apply_status(Burning::new(BURN_DAMAGE_PER_SECOND, BURN_DURATION));
+                }
+                Element::Ice => {
+                    enemy.apply_status(Slowed::new(0.5, 3.0));
+                }
+                Element::Poison => {
+                    enemy.apply_status(Poisoned::new(3.0, 5.0));
+                }
+                Element::Lightning if wet => {
+                    // Arc through every wet enemy in reach of the last one hit
+                    let mut struck = HashSet::from([target]);
+                    let mut frontier = vec![(transform.translation(), amount)];
+                    while let Some((from, amount)) = frontier.pop() {
+                        let amount = amount * CHAIN_FALLOFF;
+                        for (other, other_transform, other_wet) in &enemy_query {
+                            let to = other_transform.translation();
+                            if !other_wet
+                                || struck.contains(&other)
+                                || from.distance(to) > CHAIN_RADIUS
+                            {
+                                continue;
+                            }
+                            struck.insert(other);
+                            frontier.push((to, amount));
+                            damage.write(DealDamage {
+                                target: other,
+                                amount,
+                                element,
+                            });
+                        }
+                    }
+                }
+                Element::Lightning | Element::Physical => {}
+            }
+            continue;
+        }
+
+        match element {
+            Element::Fire if flammable_query.contains(target) => {
+                commands
+                    .entity(target)
+                    .apply_status(Burning::new(BURN_DAMAGE_PER_SECOND, BURN_DURATION));
+            }
+            Element::Fire if ice_query.contains(target) => {
+                commands.entity(target).despawn();
+            }
+            Element::Ice => {
+                let Ok(aabb) = water_query.get(target) else {
+                    continue;
+                };
+                // Freezing it again just holds off the thaw
+                if let Some(mut ice) = ice_query.iter_mut().find(|ice| ice.water == target) {
+                    ice.remaining = ICE_DURATION;
+                    continue;
+                }
+                let size = aabb.size();
+                let centre = aabb.center().with_y(aabb.max.y - ICE_THICKNESS / 2.0);
+                commands.spawn((
+                    Mesh3d(meshes.add(Cuboid::new(size.x, ICE_THICKNESS, size.z))),
+                    MeshMaterial3d(materials.add(StandardMaterial {
+                        base_color: Element::Ice.color().with_alpha(0.8),
+                        alpha_mode: AlphaMode::Blend,
+                        perceptual_roughness: 0.1,
+                        ..default()
+                    })),
+                    Transform::from_translation(centre),
+                    RigidBody::Static,
+                    Collider::cuboid(size.x, ICE_THICKNESS, size.z),
+                    IceSheet {
+                        water: target,
+                        remaining: ICE_DURATION,
+                    },
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn burn_flammables(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut burning_query: Query<(Entity, &mut Flammable, &Burning, &GlobalTransform)>,
+    unlit_query: Query<(Entity, &GlobalTransform), (With<Flammable>, Without<Burning>)>,
+    victim_query: Query<
+        (Entity, &GlobalTransform),
+        (Or<(With<Player>, With<Enemy>)>, Without<Dead>),
+    >,
+) {
+    for (entity, mut flammable, burning, transform) in &mut burning_query {
+        flammable.fuel -= time.delta_secs();
+        if flammable.fuel <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if !burning.timer.ticked() {
+            continue;
+        }
+        // Keep burning until the fuel runs out
+        commands
+            .entity(entity)
+            .insert(Burning::new(0.0, BURN_DURATION));
+        // Spreads to other props and sets fire to anyone standing too close
+        let position = transform.translation();
+        for (other, other_transform) in unlit_query.iter().chain(victim_query.iter()) {
+            if other_transform.translation().distance(position) < flammable.radius {
+                commands
+                    .entity(other)
+                    .apply_status(Burning::new(BURN_DAMAGE_PER_SECOND, BURN_DURATION));
+            }
+        }
+    }
+}
+
+fn melt_ice(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut IceSheet)>) {
+    for (entity, mut ice) in &mut query {
+        ice.remaining -= time.delta_secs();
+        if ice.remaining <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn apply_damage(
+    mut events: EventReader<DealDamage>,
+    mut dealt: EventWriter<DamageDealt>,
+    mut target_query: Query<(
+        Option<&mut Health>,
+        Option<&mut Enemy>,
+        Option<&Resistances>,
+        Option<&Stats>,
+        Has<Dead>,
+    )>,
+) {
+    for DealDamage {
+        target,
+        amount,
+        element,
+    } in events.read()
+    {
+        // Corpses can't be hurt any more
+        let Ok((health, enemy, resistances, stats, false)) = target_query.get_mut(*target) else {
+            continue;
+        };
+        if health.is_none() && enemy.is_none() {
+            continue;
+        }
+        let multiplier = resistances.map_or(1.0, |resistances| resistances.multiplier(*element));
+        let amount = amount * multiplier * stats.map_or(1.0, |stats| stats.get(Stat::DamageTaken));
+        if amount > 0.0 {
+            dealt.write(DamageDealt {
+                target: *target,
+                amount,
+                element: *element,
+                crit: multiplier > 1.0,
+            });
+        }
+        if let Some(mut health) = health {
+            health.0 = (health.0 - amount).max(0.0);
+        }
+        if let Some(mut enemy) = enemy {
+            enemy.health -= amount;
+        }
+    }
+}
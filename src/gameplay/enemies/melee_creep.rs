@@ -1,9 +1,15 @@
 use crate::GameState;
+use crate::animation::CharacterRig;
+use crate::gameplay::elements::{DealDamage, Element, Resistances};
+use crate::gameplay::status_effects::{Slowed, Stunned};
 use avian3d::prelude::*;
 use bevy::prelude::*;
+use serde::Deserialize;
 
+/// Resistances come from the `Resistances` component, add one in Blender to change them.
 #[derive(Component, Reflect)]
 #[reflect(Component)]
+#[require(Resistances)]
 pub struct Enemy {
     pub health: f32,
     pub speed: f32,
@@ -14,27 +20,73 @@ pub struct Enemy {
 // Optionally, you can add a marker for melee creeps:
 #[derive(Component, Reflect)]
 #[reflect(Component)]
+#[require(CharacterRig = CharacterRig::new("characters/creep.gltf"))]
 pub struct MeleeCreep;
 
+/// XP for killing this enemy, enemies without one give `DEFAULT_XP_REWARD`
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct XpReward(pub u32);
+
+const DEFAULT_XP_REWARD: u32 = 20;
+
+/// Marks the enemy a level's boss objective is about.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct Boss;
+
+/// Which sort of enemy died, for quests to count.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnemyKind {
+    MeleeCreep,
+}
+
+/// Out of health, lying there until the corpse is cleaned up.
+#[derive(Component)]
+pub struct Dead(Timer);
+
+#[derive(Event)]
+pub struct EnemyKilled {
+    pub xp: u32,
+    /// `None` for enemies that aren't one of the known kinds
+    pub kind: Option<EnemyKind>,
+    pub boss: bool,
+    pub position: Vec3,
+}
+
 pub struct MeleeCreepPlugin;
 
 impl Plugin for MeleeCreepPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Enemy>()
             .register_type::<MeleeCreep>()
+            .register_type::<XpReward>()
+            .register_type::<Boss>()
+            .add_event::<EnemyKilled>()
             .add_systems(
                 Update,
-                (melee_creep_movement_system, melee_creep_damage_system)
+                (
+                    melee_creep_movement_system,
+                    melee_creep_damage_system,
+                    enemy_death_system,
+                )
                     .run_if(in_state(GameState::InGame)),
             );
     }
 }
 
-fn melee_creep_movement_system(
+pub(crate) fn melee_creep_movement_system(
     player_query: Query<&GlobalTransform, With<crate::player::Player>>,
     mut creep_query: Query<
-        (Entity, &Enemy, &GlobalTransform, &mut LinearVelocity),
-        With<MeleeCreep>,
+        (
+            Entity,
+            &Enemy,
+            &GlobalTransform,
+            &mut LinearVelocity,
+            Option<&Slowed>,
+            Has<Stunned>,
+        ),
+        (With<MeleeCreep>, Without<Dead>),
     >,
 ) {
     if let Some(player_transform) = player_query.iter().next() {
@@ -44,10 +96,15 @@ fn melee_creep_movement_system(
         // Collect creep positions for repulsion
         let creep_positions: Vec<_> = creep_query
             .iter()
-            .map(|(e, _, t, _)| (e, t.translation()))
+            .map(|(e, _, t, ..)| (e, t.translation()))
             .collect();
 
-        for (entity, enemy, creep_transform, mut velocity) in &mut creep_query {
+        for (entity, enemy, creep_transform, mut velocity, slowed, stunned) in &mut creep_query {
+            if stunned {
+                velocity.x = 0.0;
+                velocity.z = 0.0;
+                continue;
+            }
             let mut creep_pos = creep_transform.translation();
             creep_pos.y = 0.0;
 
@@ -69,22 +126,20 @@ fn melee_creep_movement_system(
             }
             direction += repulsion * 0.5; // Tune repulsion strength
 
-            velocity.0 = direction.normalize_or_zero() * enemy.speed;
+            let speed = enemy.speed * slowed.map_or(1.0, |slowed| slowed.factor);
+            velocity.0 = direction.normalize_or_zero() * speed;
         }
     }
 }
 
 fn melee_creep_damage_system(
     mut collision_events: EventReader<CollisionStarted>,
-    mut player_query: Query<
-        (
-            &mut crate::player::Health,
-            &mut LinearVelocity,
-            &GlobalTransform,
-        ),
-        With<crate::player::Player>,
+    mut damage: EventWriter<DealDamage>,
+    mut player_query: Query<(&mut LinearVelocity, &GlobalTransform), With<crate::player::Player>>,
+    creep_query: Query<
+        (&Enemy, &GlobalTransform),
+        (With<MeleeCreep>, Without<Dead>, Without<Stunned>),
     >,
-    creep_query: Query<(&Enemy, &GlobalTransform), With<MeleeCreep>>,
 ) {
     for CollisionStarted(e1, e2) in collision_events.read() {
         let (player_entity, creep_entity) =
@@ -96,13 +151,15 @@ fn melee_creep_damage_system(
                 continue;
             };
 
-        let (mut player_health, mut player_velocity, player_transform) =
-            player_query.get_mut(player_entity).unwrap();
+        let (mut player_velocity, player_transform) = player_query.get_mut(player_entity).unwrap();
         let (creep, creep_transform) = creep_query.get(creep_entity).unwrap();
 
         // Damage the player
-        player_health.0 -= creep.damage;
-        println!("Player hit by melee creep! Health: {}", player_health.0);
+        damage.write(DealDamage {
+            target: player_entity,
+            amount: creep.damage,
+            element: Element::Physical,
+        });
 
         // Knockback: push player away from creep
         let mut knockback_dir = player_transform.translation() - creep_transform.translation();
@@ -113,3 +170,43 @@ fn melee_creep_damage_system(
         player_velocity.0 += knockback;
     }
 }
+
+fn enemy_death_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut killed: EventWriter<EnemyKilled>,
+    alive_query: Query<
+        (
+            Entity,
+            &Enemy,
+            &GlobalTransform,
+            Option<&XpReward>,
+            Has<MeleeCreep>,
+            Has<Boss>,
+        ),
+        Without<Dead>,
+    >,
+    mut dead_query: Query<(Entity, &mut Dead, &mut LinearVelocity)>,
+) {
+    for (entity, enemy, transform, reward, melee_creep, boss) in &alive_query {
+        if enemy.health <= 0.0 {
+            killed.write(EnemyKilled {
+                xp: reward.map_or(DEFAULT_XP_REWARD, |reward| reward.0),
+                kind: melee_creep.then_some(EnemyKind::MeleeCreep),
+                boss,
+                position: transform.translation(),
+            });
+            // Leave the body around long enough for the death animation
+            commands
+                .entity(entity)
+                .insert(Dead(Timer::from_seconds(2.0, TimerMode::Once)));
+        }
+    }
+    for (entity, mut dead, mut velocity) in &mut dead_query {
+        velocity.x = 0.0;
+        velocity.z = 0.0;
+        if dead.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::camera::ThirdPersonCamera;
+use crate::gameplay::dialogue::ActiveDialogue;
+use crate::gameplay::items::InventoryOpen;
+use crate::gameplay::mechanisms::{Signal, SignalKind};
+use crate::gameplay::progression::SkillTreeOpen;
+use crate::gameplay::quests::{QuestLogOpen, StartQuest};
+use crate::map::MapOpen;
+use crate::player::Player;
+
+/// How much facing something counts against being close to it, a target dead
+/// ahead beats one a full range closer but off to the side
+const FACING_WEIGHT: f32 = 0.5;
+/// Anything this close can be used even from behind
+const BEHIND_REACH: f32 = 1.0;
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Interactable>()
+            .init_resource::<InteractionTarget>()
+            .add_event::<Interact>()
+            .add_systems(OnEnter(GameState::InGame), spawn_interact_prompt)
+            .add_systems(
+                Update,
+                (pick_interaction_target, interact, run_interact_actions)
+                    .chain()
+                    .in_set(InteractionSet)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                update_interact_prompt
+                    .after(InteractionSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// `Interact` events go out before this set finishes, anything handling them
+/// should run after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InteractionSet;
+
+/// Something the player can use with the interact key when close enough and
+/// roughly facing it. Levers, chests, NPCs and usable doors add their own.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component, Default)]
+pub struct Interactable {
+    /// Shown after the key, e.g. "Pull lever"
+    pub prompt: String,
+    pub range: f32,
+    /// How far above the origin the prompt floats
+    #[reflect(default)]
+    pub height: f32,
+    #[reflect(default)]
+    pub action: InteractAction,
+}
+
+impl Default for Interactable {
+    fn default() -> Self {
+        Self::new("Use", 2.5)
+    }
+}
+
+impl Interactable {
+    pub fn new(prompt: impl Into<String>, range: f32) -> Self {
+        Self {
+            prompt: prompt.into(),
+            range,
+            height: 1.0,
+            action: InteractAction::None,
+        }
+    }
+
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+/// Extra things to do on use, so simple switches can be set up in Blender
+/// without a component of their own.
+#[derive(Reflect, Clone, Debug, Default)]
+pub enum InteractAction {
+    /// Just the `Interact` event
+    #[default]
+    None,
+    /// Toggles whatever has this `Name` or `PlatformGroup`
+    Signal(String),
+    StartQuest(String),
+}
+
+/// The player used this entity.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Interact(pub Entity);
+
+/// Whatever the interact key would use right now.
+#[derive(Resource, Default)]
+pub struct InteractionTarget(pub Option<Entity>);
+
+#[derive(Component)]
+struct InteractPrompt;
+
+fn spawn_interact_prompt(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        Visibility::Hidden,
+        Pickable::IGNORE,
+        InteractPrompt,
+    ));
+}
+
+/// Closest thing in range, favouring what the player is facing.
+fn pick_interaction_target(
+    inventory_open: Res<InventoryOpen>,
+    skill_tree_open: Res<SkillTreeOpen>,
+    map_open: Res<MapOpen>,
+    quest_log_open: Res<QuestLogOpen>,
+    dialogue: Res<ActiveDialogue>,
+    mut target: ResMut<InteractionTarget>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    interactable_query: Query<(Entity, &Interactable, &GlobalTransform)>,
+) {
+    let busy = inventory_open.0
+        || skill_tree_open.0
+        || map_open.0
+        || quest_log_open.0
+        || dialogue.0.is_some();
+    let best = player_query
+        .single()
+        .ok()
+        .filter(|_| !busy)
+        .and_then(|player_transform| {
+            let player_pos = player_transform.translation();
+            let forward = player_transform.forward().with_y(0.0).normalize_or_zero();
+            interactable_query
+                .iter()
+                .filter_map(|(entity, interactable, transform)| {
+                    let offset = (transform.translation() - player_pos).with_y(0.0);
+                    let distance = offset.length();
+                    if distance > interactable.range {
+                        return None;
+                    }
+                    let facing = forward.dot(offset.normalize_or_zero());
+                    if facing < 0.0 && distance > BEHIND_REACH {
+                        return None;
+                    }
+                    Some((
+                        distance / interactable.range - facing * FACING_WEIGHT,
+                        entity,
+                    ))
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b))
+                .map(|(_, entity)| entity)
+        });
+    if target.0 != best {
+        target.0 = best;
+    }
+}
+
+fn interact(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    target: Res<InteractionTarget>,
+    mut interact: EventWriter<Interact>,
+) {
+    let pressed = keyboard.just_pressed(KeyCode::KeyE)
+        || gamepads
+            .iter()
+            .any(|pad| pad.just_pressed(GamepadButton::West));
+    if let (true, Some(entity)) = (pressed, target.0) {
+        interact.write(Interact(entity));
+    }
+}
+
+fn run_interact_actions(
+    mut interactions: EventReader<Interact>,
+    interactable_query: Query<&Interactable>,
+    mut signals: EventWriter<Signal>,
+    mut start_quest: EventWriter<StartQuest>,
+) {
+    for Interact(entity) in interactions.read() {
+        let Ok(interactable) = interactable_query.get(*entity) else {
+            continue;
+        };
+        match &interactable.action {
+            InteractAction::None => {}
+            InteractAction::Signal(target) => {
+                signals.write(Signal {
+                    target: target.clone(),
+                    kind: SignalKind::Toggle,
+                });
+            }
+            InteractAction::StartQuest(id) => {
+                start_quest.write(StartQuest(id.clone()));
+            }
+        }
+    }
+}
+
+/// "[E] ..." over the current target.
+fn update_interact_prompt(
+    target: Res<InteractionTarget>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<ThirdPersonCamera>>,
+    interactable_query: Query<(&Interactable, &GlobalTransform)>,
+    mut prompt_query: Query<
+        (&mut Text, &mut Node, &mut Visibility, &ComputedNode),
+        With<InteractPrompt>,
+    >,
+) {
+    let Ok((mut text, mut node, mut visibility, computed)) = prompt_query.single_mut() else {
+        return;
+    };
+    *visibility = Visibility::Hidden;
+    let (Some((interactable, transform)), Ok((camera, camera_transform))) = (
+        target
+            .0
+            .and_then(|entity| interactable_query.get(entity).ok()),
+        camera_query.single(),
+    ) else {
+        return;
+    };
+    let anchor = transform.translation() + Vec3::Y * interactable.height;
+    let Ok(screen) = camera.world_to_viewport(camera_transform, anchor) else {
+        return;
+    };
+
+    let label = format!("[E] {}", interactable.prompt);
+    if text.0 != label {
+        text.0 = label;
+    }
+    let size = computed.size() * computed.inverse_scale_factor();
+    node.left = Val::Px(screen.x - size.x / 2.0);
+    node.top = Val::Px(screen.y - size.y);
+    *visibility = Visibility::Visible;
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+
+use avian3d::prelude::*;
+use bevy::{color::palettes::css, prelude::*};
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::gameplay::stats::{Modifier, ModifierSource, Stat, Stats};
+use crate::gameplay::water::Breath;
+use crate::library::{FolderLibrary, LibraryPlugin, RonAsset};
+use crate::player::{Health, MaxHealth, Player};
+
+/// Pickups closer than this fly toward the player
+const MAGNET_RADIUS: f32 = 4.0;
+const MAGNET_SPEED: f32 = 12.0;
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(LibraryPlugin::<ItemDefinition>::new("items"))
+            .register_type::<(Pickup, Inventory, Equipment)>()
+            .init_resource::<InventoryOpen>()
+            .add_event::<UseItem>()
+            .add_event::<DropItems>()
+            .add_systems(
+                Update,
+                (drop_items, attract_pickups, collect_pickups, use_items)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Read from `assets/items/<id>.item.ron`.
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct ItemDefinition {
+    pub name: String,
+    pub description: String,
+    pub kind: ItemKind,
+    /// Most of this item one inventory slot can hold
+    pub max_stack: u32,
+    /// Applied while equipped
+    #[serde(default)]
+    pub modifiers: Vec<(Stat, Modifier)>,
+}
+
+impl RonAsset for ItemDefinition {
+    const EXTENSION: &'static str = "item.ron";
+}
+
+/// Item definitions by id, e.g. `health_potion`.
+pub type ItemLibrary = FolderLibrary<ItemDefinition>;
+
+impl ItemDefinition {
+    /// `max_stack`, except a stack always holds at least one
+    pub fn stack_size(&self) -> u32 {
+        self.max_stack.max(1)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ItemKind {
+    Consumable(ConsumableEffect),
+    Equipment(EquipmentSlot),
+    Key,
+    /// Goes in the purse rather than taking up a slot
+    Currency,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ConsumableEffect {
+    Heal(f32),
+    RestoreBreath(f32),
+    Buff {
+        stat: Stat,
+        modifier: Modifier,
+        seconds: f32,
+    },
+}
+
+#[derive(Deserialize, Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipmentSlot {
+    Weapon,
+    Armor,
+    Trinket,
+}
+
+/// Set while the inventory panel is up, so clicks don't also throw fireballs.
+#[derive(Resource, Default)]
+pub struct InventoryOpen(pub bool);
+
+#[derive(Reflect, Clone, Debug, PartialEq)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Inventory {
+    pub slots: Vec<ItemStack>,
+    pub capacity: usize,
+    pub currency: u32,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            capacity: 16,
+            currency: 0,
+        }
+    }
+}
+
+impl Inventory {
+    /// Adds as many as fit and returns how many were left over.
+    pub fn add(&mut self, id: &str, definition: &ItemDefinition, mut count: u32) -> u32 {
+        if definition.kind == ItemKind::Currency {
+            self.currency = self.currency.saturating_add(count);
+            return 0;
+        }
+        let max_stack = definition.stack_size();
+        // Top up existing stacks before starting new ones
+        for stack in self.slots.iter_mut().filter(|stack| stack.item == id) {
+            let added = count.min(max_stack.saturating_sub(stack.count));
+            stack.count += added;
+            count -= added;
+        }
+        while count > 0 && self.slots.len() < self.capacity {
+            let added = count.min(max_stack);
+            self.slots.push(ItemStack {
+                item: id.to_string(),
+                count: added,
+            });
+            count -= added;
+        }
+        count
+    }
+
+    /// How many of an item are in the bag, across every slot.
+    pub fn count(&self, id: &str) -> u32 {
+        self.slots
+            .iter()
+            .filter(|stack| stack.item == id)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    /// Whether at least one more of this item would fit.
+    pub fn has_room_for(&self, id: &str, definition: &ItemDefinition) -> bool {
+        definition.kind == ItemKind::Currency
+            || self.slots.len() < self.capacity
+            || self
+                .slots
+                .iter()
+                .any(|stack| stack.item == id && stack.count < definition.stack_size())
+    }
+
+    /// Takes `count` of an item out of the bag, or nothing at all if there aren't
+    /// that many.
+    pub fn remove(&mut self, id: &str, mut count: u32) -> bool {
+        if self.count(id) < count {
+            return false;
+        }
+        // Newest stacks first, that is where a part-filled one would be
+        for stack in self.slots.iter_mut().rev().filter(|stack| stack.item == id) {
+            let taken = count.min(stack.count);
+            stack.count -= taken;
+            count -= taken;
+        }
+        self.slots.retain(|stack| stack.count > 0);
+        true
+    }
+
+    /// Takes one out of a slot, clearing the slot once it's empty.
+    fn take_one(&mut self, slot: usize) -> Option<String> {
+        let stack = self.slots.get_mut(slot)?;
+        stack.count -= 1;
+        let item = stack.item.clone();
+        if stack.count == 0 {
+            self.slots.remove(slot);
+        }
+        Some(item)
+    }
+}
+
+/// What the player has equipped, by item id.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Equipment {
+    pub slots: HashMap<EquipmentSlot, String>,
+}
+
+/// Item lying in the world, authored in Blender. Needs a collider.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[require(Sensor, RigidBody = RigidBody::Kinematic, CollidingEntities)]
+pub struct Pickup {
+    /// Item definition id, e.g. `health_potion`
+    pub item: String,
+    pub count: u32,
+}
+
+impl Default for Pickup {
+    fn default() -> Self {
+        Self {
+            item: String::new(),
+            count: 1,
+        }
+    }
+}
+
+/// Leaves items lying on the ground as a `Pickup`, for anything given to the player
+/// that didn't fit in the bag.
+#[derive(Event, Clone, Debug)]
+pub struct DropItems {
+    pub item: String,
+    pub count: u32,
+    pub position: Vec3,
+}
+
+/// Use (or equip) whatever is in this inventory slot.
+#[derive(Event)]
+pub struct UseItem {
+    pub slot: usize,
+}
+
+fn drop_items(
+    mut commands: Commands,
+    mut drops: EventReader<DropItems>,
+    mut look: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for drop in drops.read() {
+        let (mesh, material) = look.get_or_insert_with(|| {
+            (
+                meshes.add(Sphere::new(0.2)),
+                materials.add(StandardMaterial {
+                    base_color: Color::from(css::GOLD),
+                    emissive: LinearRgba::from(css::GOLD) * 0.5,
+                    ..default()
+                }),
+            )
+        });
+        commands.spawn((
+            Pickup {
+                item: drop.item.clone(),
+                count: drop.count,
+            },
+            Collider::sphere(0.3),
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_translation(drop.position),
+        ));
+    }
+}
+
+fn attract_pickups(
+    library: Res<ItemLibrary>,
+    definitions: Res<Assets<ItemDefinition>>,
+    player_query: Query<(&Transform, &Inventory), With<Player>>,
+    mut pickup_query: Query<(&Pickup, &Transform, &mut LinearVelocity), Without<Player>>,
+) {
+    let Ok((player_transform, inventory)) = player_query.single() else {
+        return;
+    };
+    for (pickup, transform, mut velocity) in &mut pickup_query {
+        let to_player = player_transform.translation - transform.translation;
+        let distance = to_player.length();
+        // No point pulling in something we can't carry
+        let wanted = library
+            .get(&definitions, &pickup.item)
+            .is_some_and(|definition| inventory.has_room_for(&pickup.item, definition));
+        velocity.0 = if wanted && distance < MAGNET_RADIUS {
+            // Speeds up as it gets closer
+            to_player / distance.max(0.01) * MAGNET_SPEED * (1.0 - distance / MAGNET_RADIUS + 0.2)
+        } else {
+            Vec3::ZERO
+        };
+    }
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    library: Res<ItemLibrary>,
+    definitions: Res<Assets<ItemDefinition>>,
+    mut player_query: Query<(Entity, &mut Inventory), With<Player>>,
+    mut pickup_query: Query<(Entity, &mut Pickup, &CollidingEntities)>,
+) {
+    let Ok((player, mut inventory)) = player_query.single_mut() else {
+        return;
+    };
+    for (entity, mut pickup, colliding) in &mut pickup_query {
+        if !colliding.contains(&player) {
+            continue;
+        }
+        let Some(definition) = library.get(&definitions, &pickup.item) else {
+            warn!("Pickup for unknown item {}", pickup.item);
+            continue;
+        };
+        let left = inventory.add(&pickup.item, definition, pickup.count);
+        if left == 0 {
+            commands.entity(entity).despawn();
+        } else if left != pickup.count {
+            pickup.count = left;
+        }
+    }
+}
+
+fn use_items(
+    mut use_events: EventReader<UseItem>,
+    library: Res<ItemLibrary>,
+    definitions: Res<Assets<ItemDefinition>>,
+    mut player_query: Query<
+        (
+            &mut Inventory,
+            &mut Equipment,
+            &mut Health,
+            &MaxHealth,
+            &mut Breath,
+            &mut Stats,
+            &GlobalTransform,
+        ),
+        With<Player>,
+    >,
+    mut drops: EventWriter<DropItems>,
+) {
+    let Ok((
+        mut inventory,
+        mut equipment,
+        mut health,
+        max_health,
+        mut breath,
+        mut stats,
+        transform,
+    )) = player_query.single_mut()
+    else {
+        return;
+    };
+    for UseItem { slot } in use_events.read() {
+        let Some(definition) = inventory
+            .slots
+            .get(*slot)
+            .and_then(|stack| library.get(&definitions, &stack.item))
+        else {
+            continue;
+        };
+        match definition.kind {
+            ItemKind::Consumable(effect) => {
+                // Don't waste a potion at full health
+                let useful = match effect {
+                    ConsumableEffect::Heal(_) => health.0 < max_health.0,
+                    ConsumableEffect::RestoreBreath(_) => breath.current < breath.max,
+                    ConsumableEffect::Buff { .. } => true,
+                };
+                if !useful {
+                    continue;
+                }
+                inventory.take_one(*slot);
+                match effect {
+                    ConsumableEffect::Heal(amount) => {
+                        health.0 = (health.0 + amount).min(max_health.0);
+                    }
+                    ConsumableEffect::RestoreBreath(amount) => {
+                        breath.current = (breath.current + amount).min(breath.max);
+                    }
+                    ConsumableEffect::Buff {
+                        stat,
+                        modifier,
+                        seconds,
+                    } => {
+                        stats.add_modifier(stat, modifier, ModifierSource::Buff, Some(seconds));
+                    }
+                }
+            }
+            ItemKind::Equipment(equip_slot) => {
+                let Some(item) = inventory.take_one(*slot) else {
+                    continue;
+                };
+                // Whatever was equipped goes back in the bag, or on the floor if the
+                // item came off a stack and there's no slot free
+                if let Some(previous) = equipment.slots.insert(equip_slot, item) {
+                    let left = library
+                        .get(&definitions, &previous)
+                        .map_or(1, |definition| inventory.add(&previous, definition, 1));
+                    if left > 0 {
+                        drops.write(DropItems {
+                            item: previous,
+                            count: left,
+                            position: transform.translation() + Vec3::Y * 0.5,
+                        });
+                    }
+                }
+            }
+            ItemKind::Key | ItemKind::Currency => {}
+        }
+    }
+}
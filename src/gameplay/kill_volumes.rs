@@ -0,0 +1,292 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use bevy_tnua::{TnuaProximitySensor, prelude::*};
+
+use crate::GameState;
+use crate::gameplay::attacks::fireball::Fireball;
+use crate::gameplay::elements::{DamageSet, DealDamage, Element};
+use crate::gameplay::enemies::melee_creep::Enemy;
+use crate::gameplay::moving_platforms::MovingPlatform;
+use crate::player::{Health, MaxHealth, Player};
+
+pub struct KillVolumePlugin;
+
+impl Plugin for KillVolumePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<KillVolume>()
+            .register_type::<RespawnTarget>()
+            .register_type::<Checkpoint>()
+            .register_type::<RespawnPoint>()
+            .init_resource::<WorldBounds>()
+            .add_event::<PlayerFellOut>()
+            .add_systems(
+                Update,
+                (
+                    track_safe_ground,
+                    checkpoint_system,
+                    kill_volume_system,
+                    world_bounds_system,
+                    respawn_player,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                respawn_dead_player
+                    .after(DamageSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Where the player ends up after touching a kill volume.
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RespawnTarget {
+    /// Last position the player was standing on solid ground (pits, water, etc.)
+    #[default]
+    LastSafeGround,
+    /// Last checkpoint touched, falling back to the level start.
+    Checkpoint,
+}
+
+/// Sensor volume authored in Blender. Needs a collider (e.g. a `ColliderConstructor`).
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[require(Sensor, CollisionEventsEnabled)]
+pub struct KillVolume {
+    pub damage: f32,
+    /// Kill the player outright instead of applying `damage`
+    pub kill: bool,
+    pub respawn: RespawnTarget,
+}
+
+impl Default for KillVolume {
+    fn default() -> Self {
+        Self {
+            damage: 20.0,
+            kill: false,
+            respawn: RespawnTarget::LastSafeGround,
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Sensor, CollisionEventsEnabled)]
+pub struct Checkpoint;
+
+/// Lives on the player, keeps track of where to put them back.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct RespawnPoint {
+    pub safe_ground: Vec3,
+    pub checkpoint: Vec3,
+}
+
+impl RespawnPoint {
+    pub fn new(start: Vec3) -> Self {
+        Self {
+            safe_ground: start,
+            checkpoint: start,
+        }
+    }
+}
+
+/// Anything that falls below `kill_height` is treated as having hit a kill volume.
+#[derive(Resource)]
+pub struct WorldBounds {
+    pub kill_height: f32,
+    pub volume: KillVolume,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self {
+            kill_height: -30.0,
+            volume: KillVolume::default(),
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct PlayerFellOut {
+    pub damage: f32,
+    pub kill: bool,
+    pub respawn: RespawnTarget,
+}
+
+impl From<&KillVolume> for PlayerFellOut {
+    fn from(volume: &KillVolume) -> Self {
+        Self {
+            damage: volume.damage,
+            kill: volume.kill,
+            respawn: volume.respawn,
+        }
+    }
+}
+
+fn track_safe_ground(
+    mut player_query: Query<(
+        &TnuaController,
+        &TnuaProximitySensor,
+        &Transform,
+        &mut RespawnPoint,
+    )>,
+    unsafe_ground: Query<(), Or<(With<MovingPlatform>, With<KillVolume>)>>,
+) {
+    for (controller, sensor, transform, mut respawn) in &mut player_query {
+        // Only count proper standing, not the start of a jump or mid-dash
+        if controller.is_airborne().unwrap_or(true) || controller.action_name().is_some() {
+            continue;
+        }
+        let Some(ground) = &sensor.output else {
+            continue;
+        };
+        // Moving platforms won't be there when we come back
+        if unsafe_ground.contains(ground.entity) {
+            continue;
+        }
+        respawn.safe_ground = transform.translation;
+    }
+}
+
+fn checkpoint_system(
+    mut collision_events: EventReader<CollisionStarted>,
+    checkpoint_query: Query<&GlobalTransform, With<Checkpoint>>,
+    mut player_query: Query<&mut RespawnPoint, With<Player>>,
+) {
+    for CollisionStarted(e1, e2) in collision_events.read() {
+        let (player_entity, checkpoint_entity) =
+            if player_query.contains(*e1) && checkpoint_query.contains(*e2) {
+                (*e1, *e2)
+            } else if player_query.contains(*e2) && checkpoint_query.contains(*e1) {
+                (*e2, *e1)
+            } else {
+                continue;
+            };
+
+        let checkpoint_transform = checkpoint_query.get(checkpoint_entity).unwrap();
+        let mut respawn = player_query.get_mut(player_entity).unwrap();
+        respawn.checkpoint = checkpoint_transform.translation();
+    }
+}
+
+fn kill_volume_system(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionStarted>,
+    mut fell_out: EventWriter<PlayerFellOut>,
+    volume_query: Query<&KillVolume>,
+    player_query: Query<(), With<Player>>,
+    despawn_query: Query<(), Or<(With<Enemy>, With<Fireball>)>>,
+) {
+    for CollisionStarted(e1, e2) in collision_events.read() {
+        let (volume, other) = if let Ok(volume) = volume_query.get(*e1) {
+            (volume, *e2)
+        } else if let Ok(volume) = volume_query.get(*e2) {
+            (volume, *e1)
+        } else {
+            continue;
+        };
+
+        if player_query.contains(other) {
+            fell_out.write(volume.into());
+        } else if despawn_query.contains(other) {
+            commands.entity(other).despawn();
+        }
+    }
+}
+
+fn world_bounds_system(
+    mut commands: Commands,
+    bounds: Res<WorldBounds>,
+    mut fell_out: EventWriter<PlayerFellOut>,
+    player_query: Query<&Transform, With<Player>>,
+    despawn_query: Query<(Entity, &Transform), Or<(With<Enemy>, With<Fireball>)>>,
+) {
+    for transform in &player_query {
+        if transform.translation.y < bounds.kill_height {
+            fell_out.write((&bounds.volume).into());
+        }
+    }
+
+    for (entity, transform) in &despawn_query {
+        if transform.translation.y < bounds.kill_height {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn respawn_player(
+    mut fell_out: EventReader<PlayerFellOut>,
+    mut damage: EventWriter<DealDamage>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Health,
+            &MaxHealth,
+            &mut Transform,
+            &mut LinearVelocity,
+            &RespawnPoint,
+        ),
+        With<Player>,
+    >,
+) {
+    // Several volumes can fire on the same frame, only handle the first one
+    let Some(&PlayerFellOut {
+        damage: damage_amount,
+        kill,
+        respawn: target,
+    }) = fell_out.read().next()
+    else {
+        return;
+    };
+    fell_out.clear();
+
+    let Ok((player, mut health, max_health, mut transform, mut velocity, respawn)) =
+        player_query.single_mut()
+    else {
+        return;
+    };
+
+    // Dying always sends you back to the checkpoint with full health
+    if kill {
+        health.0 = max_health.0;
+        transform.translation = respawn.checkpoint;
+    } else {
+        // If this finishes the player off, respawn_dead_player moves them on again
+        damage.write(DealDamage {
+            target: player,
+            amount: damage_amount,
+            element: Element::Physical,
+        });
+        transform.translation = match target {
+            RespawnTarget::Checkpoint => respawn.checkpoint,
+            RespawnTarget::LastSafeGround => respawn.safe_ground,
+        };
+    }
+    velocity.0 = Vec3::ZERO;
+}
+
+/// Out of health, whatever did it. Back to the checkpoint with full health.
+fn respawn_dead_player(
+    mut player_query: Query<
+        (
+            &mut Health,
+            &MaxHealth,
+            &mut Transform,
+            &mut LinearVelocity,
+            &RespawnPoint,
+        ),
+        With<Player>,
+    >,
+) {
+    for (mut health, max_health, mut transform, mut velocity, respawn) in &mut player_query {
+        if health.0 > 0.0 {
+            continue;
+        }
+        health.0 = max_health.0;
+        transform.translation = respawn.checkpoint;
+        velocity.0 = Vec3::ZERO;
+    }
+}
@@ -0,0 +1,136 @@
+use avian3d::prelude::*;
+use bevy::{color::palettes::css, prelude::*};
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::gameplay::interaction::{Interact, Interactable, InteractionSet};
+use crate::gameplay::items::Pickup;
+use crate::library::{FolderLibrary, LibraryPlugin, RonAsset};
+
+/// How close the player needs to be to open a chest
+const CHEST_RANGE: f32 = 2.5;
+
+pub struct LootPlugin;
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(LibraryPlugin::<LootTable>::new("loot"))
+            .register_type::<Chest>()
+            .add_systems(
+                Update,
+                open_chests
+                    .after(InteractionSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Read from `assets/loot/<id>.loot.ron`.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct LootTable {
+    /// How many times to pick from `entries`
+    pub rolls: u32,
+    pub entries: Vec<LootEntry>,
+    /// Dropped every time on top of the rolls
+    #[serde(default)]
+    pub guaranteed: Vec<(String, u32)>,
+}
+
+impl RonAsset for LootTable {
+    const EXTENSION: &'static str = "loot.ron";
+}
+
+/// Loot tables by id, see `Chest::loot`.
+pub type LootLibrary = FolderLibrary<LootTable>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LootEntry {
+    pub item: String,
+    /// Chance relative to the other entries
+    pub weight: u32,
+    /// Inclusive range of how many drop
+    pub count: (u32, u32),
+}
+
+impl LootTable {
+    /// Item ids and counts, with repeats merged.
+    pub fn roll(&self) -> Vec<(String, u32)> {
+        let mut drops: Vec<(String, u32)> = Vec::new();
+        let mut add = |item: &str, count: u32| match drops.iter_mut().find(|(id, _)| id == item) {
+            Some((_, total)) => *total += count,
+            None => drops.push((item.to_string(), count)),
+        };
+        for (item, count) in &self.guaranteed {
+            add(item, *count);
+        }
+        let total_weight: u32 = self.entries.iter().map(|entry| entry.weight).sum();
+        if total_weight > 0 {
+            for _ in 0..self.rolls {
+                let mut pick = fastrand::u32(0..total_weight);
+                let Some(entry) = self.entries.iter().find(|entry| {
+                    let hit = pick < entry.weight;
+                    pick = pick.saturating_sub(entry.weight);
+                    hit
+                }) else {
+                    continue;
+                };
+                let (min, max) = entry.count;
+                add(&entry.item, fastrand::u32(min..=max.max(min)));
+            }
+        }
+        drops.retain(|(_, count)| *count > 0);
+        drops
+    }
+}
+
+/// Opened once with the interact key, throwing out a roll of its loot table.
+/// Authored in Blender.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Interactable = Interactable::new("Open chest", CHEST_RANGE))]
+pub struct Chest {
+    /// Loot table id, e.g. `common_chest`
+    pub loot: String,
+}
+
+fn open_chests(
+    mut commands: Commands,
+    mut interactions: EventReader<Interact>,
+    library: Res<LootLibrary>,
+    tables: Res<Assets<LootTable>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    chest_query: Query<(&Chest, &GlobalTransform)>,
+) {
+    for Interact(entity) in interactions.read() {
+        let Ok((chest, transform)) = chest_query.get(*entity) else {
+            continue;
+        };
+        let Some(table) = library.get(&tables, &chest.loot) else {
+            warn!("Chest wants unknown loot table {}", chest.loot);
+            continue;
+        };
+        // Can't be opened twice
+        commands.entity(*entity).remove::<Interactable>();
+
+        let mesh = meshes.add(Sphere::new(0.2));
+        let material = materials.add(StandardMaterial {
+            base_color: Color::from(css::GOLD),
+            emissive: LinearRgba::from(css::GOLD) * 0.5,
+            ..default()
+        });
+        let origin = transform.translation() + Vec3::Y * 0.8;
+        for (item, count) in table.roll() {
+            // Scattered a little so they don't all land in one spot
+            let angle = fastrand::f32() * std::f32::consts::TAU;
+            let spread = Vec3::new(angle.cos(), 0.0, angle.sin()) * 0.4;
+            commands.spawn((
+                Pickup { item, count },
+                Collider::sphere(0.3),
+                Mesh3d(mesh.clone()),
+                MeshMaterial3d(material.clone()),
+                Transform::from_translation(origin + spread),
+            ));
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use bevy::prelude::*;
+use bevy_tnua::TnuaProximitySensor;
+
+use crate::GameState;
+use crate::gameplay::interaction::{Interact, Interactable, InteractionSet};
+use crate::gameplay::items::Inventory;
+use crate::gameplay::moving_platforms::PlatformGroup;
+use crate::player::Player;
+
+/// How close the player needs to be to pull a lever
+const LEVER_RANGE: f32 = 2.5;
+/// Doors are wide, so a bit further for those
+const DOOR_RANGE: f32 = 3.0;
+
+pub struct MechanismPlugin;
+
+impl Plugin for MechanismPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Activatable>()
+            .register_type::<PressurePlate>()
+            .register_type::<Lever>()
+            .register_type::<Door>()
+            .add_event::<Signal>()
+            .add_systems(
+                Update,
+                (
+                    (pressure_plate_system, lever_system, use_doors),
+                    route_signals,
+                    (door_system, door_prompts),
+                )
+                    .chain()
+                    .in_set(MechanismSet)
+                    .after(InteractionSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Signals are routed before this set finishes, anything reacting to `Activatable`
+/// should run after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MechanismSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalKind {
+    Activate,
+    Deactivate,
+    Toggle,
+}
+
+/// Sent to every entity whose `Name` or `PlatformGroup` matches `target`.
+#[derive(Event, Debug, Clone)]
+pub struct Signal {
+    pub target: String,
+    pub kind: SignalKind,
+}
+
+/// Something that can be switched on and off by signals, e.g. a moving platform
+/// that should wait for a switch, a door or a bridge.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct Activatable {
+    pub active: bool,
+    /// Switch back off after finishing one movement (elevators)
+    #[reflect(default)]
+    pub auto_reset: bool,
+}
+
+/// Sends `Activate` when the player stands on it. Put one on a moving platform
+/// targeting its own group to make it start when stood on.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct PressurePlate {
+    pub target: String,
+    /// Send `Deactivate` again when the player steps off
+    #[reflect(default)]
+    pub hold: bool,
+    #[reflect(ignore)]
+    pub pressed: bool,
+}
+
+/// Flipped by the player with the interact key, toggles its target each time.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Interactable = Interactable::new(lever_prompt(false), LEVER_RANGE))]
+pub struct Lever {
+    pub target: String,
+    #[reflect(default)]
+    pub on: bool,
+}
+
+/// Moves between its authored (closed) pose and an open pose while `Activatable`
+/// is active. Also works for drawbridges by setting `open_rotation`.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[require(Activatable)]
+pub struct Door {
+    pub open_offset: Vec3,
+    /// Euler XYZ in degrees
+    #[reflect(default)]
+    pub open_rotation: Vec3,
+    /// Fraction of the full movement per second
+    pub speed: f32,
+    /// Can also be opened and closed by hand, not just by signals
+    #[reflect(default)]
+    pub usable: bool,
+    /// Item id the player needs to open it by hand, empty for none
+    #[reflect(default)]
+    pub key: String,
+}
+
+impl Default for Door {
+    fn default() -> Self {
+        Self {
+            open_offset: Vec3::Y * 3.0,
+            open_rotation: Vec3::ZERO,
+            speed: 1.0,
+            usable: false,
+            key: String::new(),
+        }
+    }
+}
+
+/// Runtime state for `Door`, how far open it is and where it started.
+#[derive(Component)]
+pub struct DoorState {
+    pub closed: Transform,
+    pub progress: f32,
+}
+
+/// Walks up the hierarchy since the collider is often a child of the tagged object.
+fn find_plate(
+    mut entity: Entity,
+    plates: &Query<(Entity, &mut PressurePlate)>,
+    parents: &Query<&ChildOf>,
+) -> Option<Entity> {
+    loop {
+        if plates.contains(entity) {
+            return Some(entity);
+        }
+        entity = parents.get(entity).ok()?.parent();
+    }
+}
+
+fn pressure_plate_system(
+    player_query: Query<&TnuaProximitySensor, With<Player>>,
+    mut plate_query: Query<(Entity, &mut PressurePlate)>,
+    parents: Query<&ChildOf>,
+    mut signals: EventWriter<Signal>,
+) {
+    let standing_on = player_query
+        .single()
+        .ok()
+        .and_then(|sensor| sensor.output.as_ref())
+        .and_then(|ground| find_plate(ground.entity, &plate_query, &parents));
+
+    for (entity, mut plate) in &mut plate_query {
+        let pressed = standing_on == Some(entity);
+        if pressed == plate.pressed {
+            continue;
+        }
+        plate.pressed = pressed;
+
+        if pressed {
+            signals.write(Signal {
+                target: plate.target.clone(),
+                kind: SignalKind::Activate,
+            });
+        } else if plate.hold {
+            signals.write(Signal {
+                target: plate.target.clone(),
+                kind: SignalKind::Deactivate,
+            });
+        }
+    }
+}
+
+fn lever_system(
+    mut interactions: EventReader<Interact>,
+    mut lever_query: Query<(&mut Lever, &mut Interactable)>,
+    mut signals: EventWriter<Signal>,
+) {
+    for Interact(entity) in interactions.read() {
+        let Ok((mut lever, mut interactable)) = lever_query.get_mut(*entity) else {
+            continue;
+        };
+        lever.on = !lever.on;
+        signals.write(Signal {
+            target: lever.target.clone(),
+            kind: SignalKind::Toggle,
+        });
+        interactable.prompt = lever_prompt(lever.on).to_string();
+    }
+}
+
+fn lever_prompt(on: bool) -> &'static str {
+    if on { "Reset lever" } else { "Pull lever" }
+}
+
+/// Opens and closes usable doors, as long as the player has the key.
+fn use_doors(
+    mut interactions: EventReader<Interact>,
+    player_query: Query<&Inventory, With<Player>>,
+    mut door_query: Query<(&Door, &mut Activatable)>,
+) {
+    let Ok(inventory) = player_query.single() else {
+        return;
+    };
+    for Interact(entity) in interactions.read() {
+        let Ok((door, mut activatable)) = door_query.get_mut(*entity) else {
+            continue;
+        };
+        if door.key.is_empty() || inventory.count(&door.key) > 0 {
+            activatable.active = !activatable.active;
+        }
+    }
+}
+
+/// Keeps a usable door's prompt in step with whether it's open or still locked.
+fn door_prompts(
+    player_query: Query<Ref<Inventory>, With<Player>>,
+    mut door_query: Query<(Ref<Activatable>, &Door, &mut Interactable)>,
+) {
+    let Ok(inventory) = player_query.single() else {
+        return;
+    };
+    for (activatable, door, mut interactable) in &mut door_query {
+        if !activatable.is_changed() && !inventory.is_changed() && !interactable.is_added() {
+            continue;
+        }
+        let prompt = if activatable.active {
+            "Close door"
+        } else if !door.key.is_empty() && inventory.count(&door.key) == 0 {
+            "Locked"
+        } else {
+            "Open door"
+        };
+        if interactable.prompt != prompt {
+            interactable.prompt = prompt.to_string();
+        }
+    }
+}
+
+fn route_signals(
+    mut signals: EventReader<Signal>,
+    mut target_query: Query<(&mut Activatable, Option<&Name>, Option<&PlatformGroup>)>,
+) {
+    for signal in signals.read() {
+        let mut matched = false;
+        for (mut activatable, name, group) in &mut target_query {
+            let by_name = name.is_some_and(|name| name.as_str() == signal.target);
+            let by_group = group.is_some_and(|group| group.0 == signal.target);
+            if !(by_name || by_group) {
+                continue;
+            }
+            matched = true;
+            activatable.active = match signal.kind {
+                SignalKind::Activate => true,
+                SignalKind::Deactivate => false,
+                SignalKind::Toggle => !activatable.active,
+            };
+        }
+        if !matched {
+            warn!(
+                "Signal sent to '{}' but nothing listens to it",
+                signal.target
+            );
+        }
+    }
+}
+
+fn door_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    new_doors: Query<(Entity, &Door, &Transform), Without<DoorState>>,
+    mut door_query: Query<(&Door, &mut Activatable, &mut DoorState, &mut Transform)>,
+) {
+    for (entity, door, transform) in &new_doors {
+        commands.entity(entity).insert(DoorState {
+            closed: *transform,
+            progress: 0.0,
+        });
+        if door.usable {
+            commands
+                .entity(entity)
+                .insert(Interactable::new("Open door", DOOR_RANGE).with_height(1.5));
+        }
+    }
+
+    for (door, mut activatable, mut state, mut transform) in &mut door_query {
+        let goal = if activatable.active { 1.0 } else { 0.0 };
+        if state.progress == goal {
+            continue;
+        }
+        let step = door.speed * time.delta_secs();
+        state.progress = if goal > state.progress {
+            (state.progress + step).min(goal)
+        } else {
+            (state.progress - step).max(goal)
+        };
+
+        let open_rotation = Quat::from_euler(
+            EulerRot::XYZ,
+            door.open_rotation.x.to_radians(),
+            door.open_rotation.y.to_radians(),
+            door.open_rotation.z.to_radians(),
+        );
+        let t = state.progress * state.progress * (3.0 - 2.0 * state.progress);
+        transform.translation = state.closed.translation + door.open_offset * t;
+        transform.rotation = state.closed.rotation * Quat::IDENTITY.slerp(open_rotation, t);
+
+        if state.progress >= 1.0 && activatable.auto_reset {
+            activatable.active = false;
+        }
+    }
+}
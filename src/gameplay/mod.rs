@@ -1,3 +1,17 @@
 pub mod attacks;
+pub mod dialogue;
+pub mod elements;
 pub mod enemies;
+pub mod interaction;
+pub mod items;
+pub mod kill_volumes;
+pub mod loot;
+pub mod mechanisms;
 pub mod moving_platforms;
+pub mod progression;
+pub mod quests;
+pub mod stats;
+pub mod status_effects;
+pub mod traversal;
+pub mod vitals;
+pub mod water;
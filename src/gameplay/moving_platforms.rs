@@ -1,17 +1,37 @@
-use avian3d::prelude::LinearVelocity;
+use avian3d::prelude::{AngularVelocity, LinearVelocity, Position, Rotation};
 use bevy::prelude::*;
-use bevy_tnua::math::{AdjustPrecision, Float, Vector3};
+use bevy_tnua::math::{AdjustPrecision, Float, Quaternion, Vector3};
 
 use crate::GameState;
+use crate::gameplay::mechanisms::Activatable;
 //use crate::dev_utils::debug_print_game_state;
 
-pub struct MovingPlatformPlugin;
+#[derive(Default)]
+pub struct MovingPlatformPlugin {
+    /// Stay on the loading screen instead of running a level with broken platform paths
+    pub strict: bool,
+}
 
 impl Plugin for MovingPlatformPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<MovingPlatform>()
+            .register_type::<PlatformMode>()
+            .register_type::<PlatformCurve>()
+            .register_type::<PlatformEasing>()
             .register_type::<PlatformWaypoint>()
             .register_type::<PlatformGroup>()
+            .insert_resource(PlatformValidation {
+                strict: self.strict,
+                ..default()
+            })
+            // Checked as soon as the level spawns, so strict mode can hold it back on
+            // the loading screen, and again whenever a hot reload changes the paths
+            .add_systems(
+                Update,
+                (reset_platform_validation, validate_platform_paths)
+                    .chain()
+                    .in_set(PlatformValidationSet),
+            )
             .add_systems(
                 OnEnter(GameState::InGame),
                 (
@@ -20,24 +40,78 @@ impl Plugin for MovingPlatformPlugin {
                     //debug_print_game_state,
                 ),
             )
+            // Same schedule as the character controller so riders and platforms stay in step
             .add_systems(
-                Update,
+                FixedUpdate,
                 moving_platform_system.run_if(in_state(GameState::InGame)),
             );
     }
 }
 
+/// What a platform does once it reaches the last waypoint.
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlatformMode {
+    /// Go back to the start and around again
+    #[default]
+    Loop,
+    /// Reverse through the waypoints
+    PingPong,
+    /// Stop at the last waypoint
+    Once,
+}
+
+/// Shape of the path between waypoints.
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlatformCurve {
+    #[default]
+    Linear,
+    /// Smooth curve passing through every waypoint
+    CatmullRom,
+    /// Curve whose tangents follow each waypoint's forward (-Z) axis, rotate the
+    /// waypoints in Blender to shape it
+    Bezier,
+}
+
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlatformEasing {
+    #[default]
+    Linear,
+    /// Accelerate out of and decelerate into each waypoint
+    EaseInOut,
+}
+
+impl PlatformEasing {
+    fn apply(self, t: Float) -> Float {
+        match self {
+            PlatformEasing::Linear => t,
+            PlatformEasing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 pub struct MovingPlatform {
     pub current_leg: usize,
     pub speed: Float,
+    #[reflect(default)]
+    pub mode: PlatformMode,
+    #[reflect(default)]
+    pub curve: PlatformCurve,
+    #[reflect(default)]
+    pub easing: PlatformEasing,
+    /// Turn to match each waypoint's orientation along the way
+    #[reflect(default)]
+    pub rotate: bool,
 }
 
 #[derive(Component, Reflect, Default)]
 #[reflect(Component, Default)]
 pub struct PlatformWaypoint {
     pub index: usize,
+    /// Seconds to wait here before moving on
+    #[reflect(default)]
+    pub dwell: Float,
 }
 
 #[derive(Component, Reflect)]
@@ -47,6 +121,234 @@ pub struct PlatformGroup(pub String);
 #[derive(Component)]
 pub struct PlatformPath {
     pub locations: Vec<Vector3>,
+    pub rotations: Vec<Quaternion>,
+    pub dwell: Vec<Float>,
+}
+
+/// Runs the platform path checks, anything waiting on them should run after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlatformValidationSet;
+
+/// Authoring problems found by `validate_platform_paths` when the level loads.
+#[derive(Resource, Default)]
+pub struct PlatformValidation {
+    /// Don't leave the loading state while there are errors
+    pub strict: bool,
+    /// Set once the level's platforms have been looked over
+    pub checked: bool,
+    pub errors: Vec<String>,
+}
+
+impl PlatformValidation {
+    /// Whether the level is fine to start.
+    pub fn passed(&self) -> bool {
+        self.checked && (!self.strict || self.errors.is_empty())
+    }
+}
+
+/// Runtime progress along the current leg, `MovingPlatform::current_leg` is the
+/// waypoint being travelled to.
+#[derive(Component, Default)]
+pub struct PlatformMotion {
+    pub from: usize,
+    pub travelled: Float,
+    pub dwell_remaining: Float,
+    pub reversing: bool,
+    pub finished: bool,
+}
+
+impl PlatformPath {
+    fn index(&self, index: isize, looping: bool) -> usize {
+        let len = self.locations.len() as isize;
+        if looping {
+            index.rem_euclid(len) as usize
+        } else {
+            index.clamp(0, len - 1) as usize
+        }
+    }
+
+    /// Position `t` (0..1) of the way along the leg between two neighbouring waypoints.
+    pub fn sample(
+        &self,
+        curve: PlatformCurve,
+        looping: bool,
+        from: usize,
+        to: usize,
+        t: Float,
+    ) -> Vector3 {
+        let p1 = self.locations[from];
+        let p2 = self.locations[to];
+        match curve {
+            PlatformCurve::Linear => p1.lerp(p2, t),
+            PlatformCurve::CatmullRom => {
+                // Neighbours in the direction of travel
+                let step = if self.index(from as isize + 1, looping) == to {
+                    1
+                } else {
+                    -1
+                };
+                let p0 = self.locations[self.index(from as isize - step, looping)];
+                let p3 = self.locations[self.index(to as isize + step, looping)];
+                let t2 = t * t;
+                let t3 = t2 * t;
+                0.5 * ((2.0 * p1)
+                    + (p2 - p0) * t
+                    + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                    + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+            }
+            PlatformCurve::Bezier => {
+                let handle = p1.distance(p2) / 3.0;
+                let mut out_dir = self.rotations[from] * Vector3::NEG_Z;
+                let mut in_dir = self.rotations[to] * Vector3::NEG_Z;
+                // Waypoints only point one way, flip the handles when heading backwards
+                if out_dir.dot(p2 - p1) < 0.0 {
+                    out_dir = -out_dir;
+                }
+                if in_dir.dot(p2 - p1) < 0.0 {
+                    in_dir = -in_dir;
+                }
+                let c1 = p1 + out_dir * handle;
+                let c2 = p2 - in_dir * handle;
+                let u = 1.0 - t;
+                u * u * u * p1 + 3.0 * u * u * t * c1 + 3.0 * u * t * t * c2 + t * t * t * p2
+            }
+        }
+    }
+
+    pub fn leg_length(&self, curve: PlatformCurve, looping: bool, from: usize, to: usize) -> Float {
+        if curve == PlatformCurve::Linear {
+            return self.locations[from].distance(self.locations[to]);
+        }
+        const SAMPLES: usize = 16;
+        let mut length = 0.0;
+        let mut previous = self.locations[from];
+        for i in 1..=SAMPLES {
+            let point = self.sample(curve, looping, from, to, i as Float / SAMPLES as Float);
+            length += previous.distance(point);
+            previous = point;
+        }
+        length
+    }
+}
+
+fn describe(entity: Entity, name: Option<&Name>) -> String {
+    match name {
+        Some(name) => format!("'{name}'"),
+        None => format!("{entity}"),
+    }
+}
+
+/// Waypoints turning up, changing or going away means the paths need another look.
+fn reset_platform_validation(
+    mut validation: ResMut<PlatformValidation>,
+    changed_query: Query<(), Or<(Changed<PlatformWaypoint>, Changed<PlatformGroup>)>>,
+    mut removed: RemovedComponents<PlatformWaypoint>,
+) {
+    let removed = removed.read().count() > 0;
+    if validation.checked && (removed || !changed_query.is_empty()) {
+        validation.checked = false;
+        validation.errors.clear();
+    }
+}
+
+fn validate_platform_paths(
+    mut validation: ResMut<PlatformValidation>,
+    platform_query: Query<(Entity, Option<&Name>, Option<&PlatformGroup>), With<MovingPlatform>>,
+    waypoint_query: Query<(
+        Entity,
+        Option<&Name>,
+        Option<&PlatformGroup>,
+        &PlatformWaypoint,
+    )>,
+) {
+    // Waits for the level to spawn, then only looks again once something changes
+    if validation.checked || waypoint_query.is_empty() {
+        return;
+    }
+    let mut errors = Vec::new();
+
+    let mut platform_groups = std::collections::HashSet::new();
+    for (entity, name, group) in &platform_query {
+        match group {
+            Some(group) => {
+                platform_groups.insert(group.0.as_str());
+            }
+            None => errors.push(format!(
+                "Platform {} has MovingPlatform but no PlatformGroup",
+                describe(entity, name)
+            )),
+        }
+    }
+
+    // index -> waypoint names, per group
+    let mut group_to_indices: std::collections::BTreeMap<
+        &str,
+        std::collections::BTreeMap<usize, Vec<String>>,
+    > = std::collections::BTreeMap::new();
+    for (entity, name, group, waypoint) in &waypoint_query {
+        let Some(group) = group else {
+            errors.push(format!(
+                "Waypoint {} has no PlatformGroup",
+                describe(entity, name)
+            ));
+            continue;
+        };
+        if !platform_groups.contains(group.0.as_str()) {
+            errors.push(format!(
+                "Waypoint {} is in group '{}' but no platform uses that group",
+                describe(entity, name),
+                group.0
+            ));
+        }
+        group_to_indices
+            .entry(group.0.as_str())
+            .or_default()
+            .entry(waypoint.index)
+            .or_default()
+            .push(describe(entity, name));
+    }
+
+    for (group, indices) in &group_to_indices {
+        for (index, names) in indices {
+            if names.len() > 1 {
+                errors.push(format!(
+                    "Group '{group}' has duplicate waypoint index {index}: {}",
+                    names.join(", ")
+                ));
+            }
+        }
+        // Indices don't have to start anywhere in particular, but shouldn't skip any
+        let (Some(first), Some(last)) = (indices.keys().next(), indices.keys().last()) else {
+            continue;
+        };
+        for index in *first..*last {
+            if !indices.contains_key(&index) {
+                errors.push(format!("Group '{group}' is missing waypoint index {index}"));
+            }
+        }
+    }
+
+    for group in &platform_groups {
+        if !group_to_indices.contains_key(group) {
+            errors.push(format!("No waypoints found for platform group '{group}'"));
+        }
+    }
+
+    for message in &errors {
+        if validation.strict {
+            error!("{message}");
+        } else {
+            warn!("{message}");
+        }
+    }
+    if validation.strict && !errors.is_empty() {
+        error!(
+            "{} platform path error(s), refusing to start the level",
+            errors.len()
+        );
+    }
+    validation.checked = true;
+    validation.errors = errors;
 }
 
 fn setup_platform_paths(
@@ -55,65 +357,304 @@ fn setup_platform_paths(
     waypoint_query: Query<(&PlatformGroup, &PlatformWaypoint, &GlobalTransform)>,
 ) {
     // Collect all waypoints by group name
-    let mut group_to_waypoints: std::collections::HashMap<String, Vec<(usize, Vector3)>> =
-        std::collections::HashMap::new();
+    let mut group_to_waypoints: std::collections::HashMap<
+        String,
+        Vec<(usize, Vector3, Quaternion, Float)>,
+    > = std::collections::HashMap::new();
 
     for (group, waypoint, transform) in &waypoint_query {
+        let (_, rotation, translation) = transform.to_scale_rotation_translation();
         group_to_waypoints
             .entry(group.0.clone())
             .or_default()
-            .push((waypoint.index, transform.translation().adjust_precision()));
+            .push((
+                waypoint.index,
+                translation.adjust_precision(),
+                rotation.adjust_precision(),
+                waypoint.dwell,
+            ));
     }
 
     // Assign sorted waypoints to each platform by group name, with initial position as index 0
     for (platform_entity, group, platform_transform) in &platform_query {
-        let mut locations = Vec::new();
+        let (_, rotation, translation) = platform_transform.to_scale_rotation_translation();
         // Insert the platform's initial position as the first location
-        locations.push(platform_transform.translation().adjust_precision());
+        let mut path = PlatformPath {
+            locations: vec![translation.adjust_precision()],
+            rotations: vec![rotation.adjust_precision()],
+            dwell: vec![0.0],
+        };
 
         if let Some(mut waypoints) = group_to_waypoints.get(&group.0).cloned() {
             // Sort by index
-            waypoints.sort_by_key(|(index, _)| *index);
+            waypoints.sort_by_key(|(index, ..)| *index);
             // Append the sorted waypoints
-            locations.extend(waypoints.iter().map(|(_, pos)| *pos));
-        } else {
-            warn!("No waypoints found for platform group '{}'", group.0);
+            for (_, location, rotation, dwell) in waypoints {
+                path.locations.push(location);
+                path.rotations.push(rotation);
+                path.dwell.push(dwell);
+            }
         }
 
         commands
             .entity(platform_entity)
-            .insert(PlatformPath { locations });
+            .insert((path, PlatformMotion::default()));
     }
 }
 
 fn moving_platform_system(
-    time: Res<Time>,
+    time: Res<Time<Fixed>>,
     mut query: Query<(
         &mut MovingPlatform,
-        &Transform,
+        &mut PlatformMotion,
+        &Position,
+        &Rotation,
         &PlatformPath,
         &mut LinearVelocity,
+        &mut AngularVelocity,
+        Option<&mut Activatable>,
     )>,
 ) {
-    for (mut moving_platform, transform, path, mut linear_velocity) in query.iter_mut() {
-        if path.locations.is_empty() {
-            linear_velocity.0 = Vec3::ZERO;
+    let dt = time.delta_secs().adjust_precision();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (
+        mut platform,
+        mut motion,
+        position,
+        rotation,
+        path,
+        mut linear_velocity,
+        mut angular_velocity,
+        mut activatable,
+    ) in query.iter_mut()
+    {
+        // Platforms with `Activatable` wait for a switch
+        if activatable.as_ref().is_some_and(|a| !a.active) {
+            linear_velocity.0 = Vector3::ZERO;
+            angular_velocity.0 = Vector3::ZERO;
             continue;
         }
-        let current = transform.translation;
-        let target = path.locations[moving_platform.current_leg];
-        let vec_to = target - current;
-        let speed = moving_platform.speed;
-        let step = vec_to.normalize_or_zero() * speed;
-
-        // If we're close enough, snap to the target and advance
-        if vec_to.length() <= step.length() * time.delta_secs() {
-            // Snap to target by zeroing velocity, will be updated next frame
-            linear_velocity.0 = Vec3::ZERO;
-            moving_platform.current_leg = (moving_platform.current_leg + 1) % path.locations.len();
-        } else {
-            // Set velocity toward the target
-            linear_velocity.0 = step;
+
+        let arrived_before = motion.from;
+        (linear_velocity.0, angular_velocity.0) =
+            step_platform(&mut platform, &mut motion, path, position.0, rotation.0, dt);
+
+        if motion.from != arrived_before
+            && let Some(activatable) = activatable.as_mut().filter(|a| a.auto_reset)
+        {
+            activatable.active = false;
+        }
+    }
+}
+
+/// Advances a platform by one fixed step and returns the linear and angular velocity
+/// that put it exactly where it should be at the end of the step. Velocities are
+/// always relative to where the body actually is, so errors never build up.
+pub fn step_platform(
+    platform: &mut MovingPlatform,
+    motion: &mut PlatformMotion,
+    path: &PlatformPath,
+    position: Vector3,
+    rotation: Quaternion,
+    dt: Float,
+) -> (Vector3, Vector3) {
+    let len = path.locations.len();
+    if len < 2 || motion.finished || platform.speed <= 0.0 {
+        return (Vector3::ZERO, Vector3::ZERO);
+    }
+
+    // Negative dwell is time left over from overshooting the last waypoint
+    motion.dwell_remaining -= dt;
+    if motion.dwell_remaining > 0.0 {
+        return (Vector3::ZERO, Vector3::ZERO);
+    }
+    let dt_left = -motion.dwell_remaining;
+    motion.dwell_remaining = 0.0;
+
+    let looping = platform.mode == PlatformMode::Loop;
+    let to = platform.current_leg.min(len - 1);
+    let from = motion.from;
+    let leg_length = path.leg_length(platform.curve, looping, from, to);
+    motion.travelled += platform.speed * dt_left;
+
+    let t = if leg_length > 0.0 {
+        (motion.travelled / leg_length).min(1.0)
+    } else {
+        1.0
+    };
+    let eased = platform.easing.apply(t);
+    // Lands exactly on the waypoint at the end of the arriving step
+    let target = path.sample(platform.curve, looping, from, to, eased);
+    let target_rotation = path.rotations[from].slerp(path.rotations[to], eased);
+
+    let linear_velocity = (target - position) / dt;
+    let angular_velocity = if platform.rotate {
+        let delta = target_rotation * rotation.inverse();
+        // Take the short way round
+        let delta = if delta.w < 0.0 { -delta } else { delta };
+        delta.to_scaled_axis() / dt
+    } else {
+        Vector3::ZERO
+    };
+
+    if t >= 1.0 {
+        // Arrived, carry the overshoot into the next leg so loops don't fall behind
+        let overshoot = (motion.travelled - leg_length).max(0.0) / platform.speed;
+        motion.travelled = 0.0;
+        motion.dwell_remaining = path.dwell[to] - overshoot;
+        motion.from = to;
+        platform.current_leg = match platform.mode {
+            PlatformMode::Loop => (to + 1) % len,
+            PlatformMode::PingPong => {
+                if to == len - 1 {
+                    motion.reversing = true;
+                } else if to == 0 {
+                    motion.reversing = false;
+                }
+                if motion.reversing { to - 1 } else { to + 1 }
+            }
+            PlatformMode::Once => {
+                if to == len - 1 {
+                    motion.finished = true;
+                }
+                (to + 1).min(len - 1)
+            }
+        };
+    }
+
+    (linear_velocity, angular_velocity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square_path() -> PlatformPath {
+        PlatformPath {
+            locations: vec![
+                Vector3::new(0.0, 2.0, 0.0),
+                Vector3::new(10.0, 2.0, 0.0),
+                Vector3::new(10.0, 2.0, 10.0),
+                Vector3::new(0.0, 2.0, 10.0),
+            ],
+            rotations: vec![Quaternion::IDENTITY; 4],
+            dwell: vec![0.0, 0.0, 0.5, 0.0],
+        }
+    }
+
+    fn distance_to_path(path: &PlatformPath, point: Vector3) -> Float {
+        let len = path.locations.len();
+        (0..len)
+            .map(|i| {
+                let a = path.locations[i];
+                let b = path.locations[(i + 1) % len];
+                let t = ((point - a).dot(b - a) / (b - a).length_squared()).clamp(0.0, 1.0);
+                point.distance(a + (b - a) * t)
+            })
+            .fold(Float::INFINITY, Float::min)
+    }
+
+    #[test]
+    fn looping_platform_does_not_drift_after_1000_loops() {
+        let path = square_path();
+        let mut platform = MovingPlatform {
+            current_leg: 1,
+            speed: 7.3,
+            mode: PlatformMode::Loop,
+            curve: PlatformCurve::Linear,
+            easing: PlatformEasing::EaseInOut,
+            rotate: false,
+        };
+        let mut motion = PlatformMotion::default();
+        let mut position = path.locations[0];
+        let dt = 1.0 / 64.0;
+
+        let mut loops = 0;
+        let mut max_error: Float = 0.0;
+        while loops < 1000 {
+            let was_heading_to = platform.current_leg;
+            let (velocity, _) = step_platform(
+                &mut platform,
+                &mut motion,
+                &path,
+                position,
+                Quaternion::IDENTITY,
+                dt,
+            );
+            // Same integration the physics engine does for kinematic bodies
+            position += velocity * dt;
+            max_error = max_error.max(distance_to_path(&path, position));
+
+            if platform.current_leg != was_heading_to {
+                // Every arrival has to land on the waypoint itself
+                let waypoint = path.locations[was_heading_to];
+                assert!(
+                    position.distance(waypoint) < 1e-3,
+                    "missed waypoint {was_heading_to}: {position} vs {waypoint}"
+                );
+                if was_heading_to == 0 {
+                    loops += 1;
+                }
+            }
+        }
+
+        assert!(max_error < 1e-3, "left the path by {max_error}");
+        assert!(position.distance(path.locations[0]) < 1e-3);
+    }
+
+    #[test]
+    fn ping_pong_reverses_and_once_stops() {
+        let path = square_path();
+        let mut platform = MovingPlatform {
+            current_leg: 1,
+            speed: 5.0,
+            mode: PlatformMode::PingPong,
+            curve: PlatformCurve::Linear,
+            easing: PlatformEasing::Linear,
+            rotate: false,
+        };
+        let mut motion = PlatformMotion::default();
+        let mut position = path.locations[0];
+        let mut visited = Vec::new();
+        for _ in 0..10_000 {
+            let was_heading_to = platform.current_leg;
+            let (velocity, _) = step_platform(
+                &mut platform,
+                &mut motion,
+                &path,
+                position,
+                Quaternion::IDENTITY,
+                0.02,
+            );
+            position += velocity * 0.02;
+            if platform.current_leg != was_heading_to {
+                visited.push(was_heading_to);
+            }
+            if visited.len() == 6 {
+                break;
+            }
+        }
+        assert_eq!(visited, vec![1, 2, 3, 2, 1, 0]);
+
+        platform.mode = PlatformMode::Once;
+        platform.current_leg = 1;
+        motion = PlatformMotion::default();
+        position = path.locations[0];
+        for _ in 0..10_000 {
+            let (velocity, _) = step_platform(
+                &mut platform,
+                &mut motion,
+                &path,
+                position,
+                Quaternion::IDENTITY,
+                0.02,
+            );
+            position += velocity * 0.02;
         }
+        assert!(motion.finished);
+        assert!(position.distance(path.locations[3]) < 1e-3);
     }
 }
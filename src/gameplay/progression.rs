@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::GameState;
+use crate::gameplay::enemies::melee_creep::EnemyKilled;
+use crate::gameplay::stats::{Modifier, ModifierSource, Stat, Stats};
+use crate::gameplay::traversal::TraversalAbilities;
+use crate::library::{RonAsset, RonAssetPlugin};
+use crate::player::Player;
+use crate::save::{SaveData, SaveGame};
+
+/// Extra fireball damage per level above the first
+const DAMAGE_PER_LEVEL: f32 = 2.0;
+
+pub struct ProgressionPlugin;
+
+impl Plugin for ProgressionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<SkillTree>::default())
+            .register_type::<Progression>()
+            .init_resource::<SkillTreeOpen>()
+            .add_event::<LearnSkill>()
+            .add_systems(Startup, load_skill_tree)
+            .add_systems(
+                Update,
+                (
+                    restore_progression,
+                    award_xp,
+                    learn_skills,
+                    apply_progression,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Read from `assets/progression/player.skills.ron`.
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct SkillTree {
+    pub nodes: Vec<SkillNode>,
+}
+
+impl RonAsset for SkillTree {
+    const EXTENSION: &'static str = "skills.ron";
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SkillNode {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Skill points it takes to learn
+    pub cost: u32,
+    /// Ids of nodes that have to be learned first
+    #[serde(default)]
+    pub requires: Vec<String>,
+    pub effect: SkillEffect,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum SkillEffect {
+    Modifier(Stat, Modifier),
+    Unlock(Ability),
+}
+
+/// Traversal moves a skill can hand out.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum Ability {
+    DoubleJump,
+    WallJump,
+    LedgeGrab,
+    AirDash,
+    ExtraDashCharge,
+}
+
+impl SkillTree {
+    pub fn node(&self, id: &str) -> Option<&SkillNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// Whether a node could be learned right now.
+    pub fn can_learn(&self, progression: &Progression, id: &str) -> bool {
+        self.node(id).is_some_and(|node| {
+            !progression.learned(id)
+                && progression.skill_points >= node.cost
+                && node
+                    .requires
+                    .iter()
+                    .all(|required| progression.learned(required))
+        })
+    }
+}
+
+#[derive(Resource)]
+pub struct PlayerSkillTree(pub Handle<SkillTree>);
+
+/// Set while the skill tree panel is up.
+#[derive(Resource, Default)]
+pub struct SkillTreeOpen(pub bool);
+
+/// Level, XP and learned skills. Saved whenever it changes.
+#[derive(Component, Reflect, Serialize, Deserialize, Clone, Debug)]
+#[reflect(Component)]
+pub struct Progression {
+    pub level: u32,
+    /// XP into the current level
+    pub xp: u32,
+    pub skill_points: u32,
+    pub skills: Vec<String>,
+}
+
+impl Default for Progression {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            xp: 0,
+            skill_points: 0,
+            skills: Vec::new(),
+        }
+    }
+}
+
+impl Progression {
+    /// XP it takes to get from `level` to the next one
+    pub fn xp_to_next(level: u32) -> u32 {
+        100 * level
+    }
+
+    pub fn learned(&self, id: &str) -> bool {
+        self.skills.iter().any(|skill| skill == id)
+    }
+
+    /// Adds XP and returns how many levels were gained.
+    pub fn add_xp(&mut self, xp: u32) -> u32 {
+        self.xp += xp;
+        let mut gained = 0;
+        while self.xp >= Self::xp_to_next(self.level) {
+            self.xp -= Self::xp_to_next(self.level);
+            self.level += 1;
+            self.skill_points += 1;
+            gained += 1;
+        }
+        gained
+    }
+}
+
+#[derive(Event)]
+pub struct LearnSkill(pub String);
+
+fn load_skill_tree(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PlayerSkillTree(
+        asset_server.load("progression/player.skills.ron"),
+    ));
+}
+
+/// Picks up where the last session left off when the player spawns.
+fn restore_progression(
+    save: Res<SaveData>,
+    mut query: Query<&mut Progression, Added<Progression>>,
+) {
+    for mut progression in &mut query {
+        if let Some(saved) = &save.progression {
+            *progression = saved.clone();
+        }
+    }
+}
+
+fn award_xp(
+    mut killed: EventReader<EnemyKilled>,
+    mut query: Query<&mut Progression, With<Player>>,
+) {
+    let Ok(mut progression) = query.single_mut() else {
+        return;
+    };
+    for EnemyKilled { xp, .. } in killed.read() {
+        let gained = progression.add_xp(*xp);
+        if gained > 0 {
+            info!("Reached level {}", progression.level);
+        }
+    }
+}
+
+fn learn_skills(
+    mut learn: EventReader<LearnSkill>,
+    tree: Res<PlayerSkillTree>,
+    trees: Res<Assets<SkillTree>>,
+    mut query: Query<&mut Progression, With<Player>>,
+) {
+    let (Some(tree), Ok(mut progression)) = (trees.get(&tree.0), query.single_mut()) else {
+        return;
+    };
+    for LearnSkill(id) in learn.read() {
+        if !tree.can_learn(&progression, id) {
+            continue;
+        }
+        let cost = tree.node(id).map_or(0, |node| node.cost);
+        progression.skill_points -= cost;
+        progression.skills.push(id.clone());
+    }
+}
+
+/// Turns levels and learned skills into stat modifiers and unlocked moves, and
+/// saves whenever any of it changes.
+fn apply_progression(
+    tree: Res<PlayerSkillTree>,
+    trees: Res<Assets<SkillTree>>,
+    mut tree_events: EventReader<AssetEvent<SkillTree>>,
+    mut save: ResMut<SaveData>,
+    mut save_game: EventWriter<SaveGame>,
+    mut query: Query<(Ref<Progression>, &mut Stats, &mut TraversalAbilities), With<Player>>,
+) {
+    // Editing the tree file while playing should take effect straight away
+    let tree_changed = tree_events
+        .read()
+        .any(|event| event.is_loaded_with_dependencies(&tree.0) || event.is_modified(&tree.0));
+    let Ok((progression, mut stats, mut abilities)) = query.single_mut() else {
+        return;
+    };
+    if !progression.is_changed() && !tree_changed {
+        return;
+    }
+
+    stats.modifiers.retain(|modifier| {
+        !matches!(
+            modifier.source,
+            ModifierSource::Level | ModifierSource::Skill
+        )
+    });
+    stats.add_modifier(
+        Stat::FireballDamage,
+        Modifier::Add(DAMAGE_PER_LEVEL * progression.level.saturating_sub(1) as f32),
+        ModifierSource::Level,
+        None,
+    );
+
+    if let Some(tree) = trees.get(&tree.0) {
+        for node in progression.skills.iter().filter_map(|id| tree.node(id)) {
+            match node.effect {
+                SkillEffect::Modifier(stat, modifier) => {
+                    stats.add_modifier(stat, modifier, ModifierSource::Skill, None);
+                }
+                // Only ever switches things on, so dev unlocks and pickups aren't undone
+                SkillEffect::Unlock(ability) => match ability {
+                    Ability::DoubleJump => abilities.air_jumps = abilities.air_jumps.max(1),
+                    Ability::WallJump => abilities.wall_jump = true,
+                    Ability::LedgeGrab => abilities.ledge_grab = true,
+                    Ability::AirDash => abilities.air_dash = true,
+                    Ability::ExtraDashCharge => {
+                        abilities.dash_charges = abilities.dash_charges.max(2);
+                    }
+                },
+            }
+        }
+    }
+
+    if progression.is_changed() {
+        save.progression = Some(progression.clone());
+        save_game.write(SaveGame);
+    }
+}
@@ -0,0 +1,362 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::GameState;
+use crate::gameplay::enemies::melee_creep::{EnemyKilled, EnemyKind};
+use crate::gameplay::items::{DropItems, Inventory, ItemDefinition, ItemLibrary};
+use crate::gameplay::progression::Progression;
+use crate::hud::HudObjective;
+use crate::library::{FolderLibrary, LibraryPlugin, RonAsset};
+use crate::player::Player;
+use crate::save::{SaveData, SaveGame};
+use crate::set_up::CurrentLevel;
+
+pub struct QuestPlugin;
+
+impl Plugin for QuestPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(LibraryPlugin::<QuestDefinition>::new("quests"))
+            .register_type::<(QuestTrigger, StartsQuest)>()
+            .init_resource::<QuestLog>()
+            .init_resource::<QuestLogOpen>()
+            .add_event::<StartQuest>()
+            .add_event::<QuestCompleted>()
+            .add_systems(OnEnter(GameState::InGame), restore_quest_log)
+            .add_systems(
+                Update,
+                (
+                    start_level_quests,
+                    start_quests,
+                    track_objectives,
+                    complete_quests,
+                    save_quest_log,
+                    show_current_objective,
+                    announce_completed_quests,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Read from `assets/quests/<id>.quest.ron`.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct QuestDefinition {
+    pub name: String,
+    pub description: String,
+    /// All of them have to be done, in any order
+    pub objectives: Vec<Objective>,
+    #[serde(default)]
+    pub rewards: QuestRewards,
+    /// Starts as soon as the game does rather than waiting for `StartQuest`
+    #[serde(default)]
+    pub auto_start: bool,
+}
+
+impl RonAsset for QuestDefinition {
+    const EXTENSION: &'static str = "quest.ron";
+}
+
+/// Quest definitions by id, the id is what `StartQuest` and `StartsQuest` take.
+pub type QuestLibrary = FolderLibrary<QuestDefinition>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Objective {
+    pub description: String,
+    pub goal: Goal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Goal {
+    Kill {
+        kind: EnemyKind,
+        count: u32,
+    },
+    /// Any kind of enemy counts
+    KillAny(u32),
+    /// Walk into the `QuestTrigger` with this id
+    Reach(String),
+    /// Have this many of an item in the inventory at once
+    Collect {
+        item: String,
+        count: u32,
+    },
+    DefeatBoss,
+}
+
+impl Goal {
+    /// Progress it takes to finish
+    pub fn target(&self) -> u32 {
+        match self {
+            Goal::Kill { count, .. } | Goal::KillAny(count) | Goal::Collect { count, .. } => *count,
+            Goal::Reach(_) | Goal::DefeatBoss => 1,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct QuestRewards {
+    pub xp: u32,
+    /// Item ids and counts, currency items go in the purse
+    pub items: Vec<(String, u32)>,
+}
+
+/// Which quests are on in the current level and how far along each objective is.
+/// Saved whenever it changes.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct QuestLog {
+    pub active: Vec<QuestProgress>,
+    /// Ids of finished quests
+    pub completed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QuestProgress {
+    pub id: String,
+    /// One per objective, filled in once the definition has loaded
+    pub progress: Vec<u32>,
+}
+
+impl QuestLog {
+    pub fn is_active(&self, id: &str) -> bool {
+        self.active.iter().any(|quest| quest.id == id)
+    }
+
+    pub fn is_completed(&self, id: &str) -> bool {
+        self.completed.iter().any(|quest| quest == id)
+    }
+}
+
+/// Set while the quest log panel is up.
+#[derive(Resource, Default)]
+pub struct QuestLogOpen(pub bool);
+
+/// Location for `Goal::Reach`, authored in Blender. Needs a collider.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Sensor, CollidingEntities)]
+pub struct QuestTrigger(pub String);
+
+/// Starts a quest when it spawns, so a level can bring its own objectives.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct StartsQuest(pub String);
+
+/// Puts a quest in the log, if it isn't there already and hasn't been done.
+#[derive(Event)]
+pub struct StartQuest(pub String);
+
+#[derive(Event)]
+pub struct QuestCompleted(pub String);
+
+fn restore_quest_log(save: Res<SaveData>, level: Res<CurrentLevel>, mut log: ResMut<QuestLog>) {
+    *log = save
+        .quests_by_level
+        .get(&level.0)
+        .cloned()
+        .unwrap_or_default();
+}
+
+/// Kicks off `auto_start` quests once their definitions are in and whenever a new
+/// level starts, plus any a level spawned with `StartsQuest`.
+fn start_level_quests(
+    library: Res<QuestLibrary>,
+    definitions: Res<Assets<QuestDefinition>>,
+    level: Res<CurrentLevel>,
+    starts_query: Query<&StartsQuest, Added<StartsQuest>>,
+    mut start: EventWriter<StartQuest>,
+) {
+    if library.is_changed() || level.is_changed() {
+        for id in library.ids() {
+            if library
+                .get(&definitions, id)
+                .is_some_and(|quest| quest.auto_start)
+            {
+                start.write(StartQuest(id.clone()));
+            }
+        }
+    }
+    for StartsQuest(id) in &starts_query {
+        start.write(StartQuest(id.clone()));
+    }
+}
+
+fn start_quests(mut start: EventReader<StartQuest>, mut log: ResMut<QuestLog>) {
+    for StartQuest(id) in start.read() {
+        if log.is_active(id) || log.is_completed(id) {
+            continue;
+        }
+        info!("Started quest {id}");
+        log.active.push(QuestProgress {
+            id: id.clone(),
+            progress: Vec::new(),
+        });
+    }
+}
+
+fn track_objectives(
+    mut killed: EventReader<EnemyKilled>,
+    library: Res<QuestLibrary>,
+    definitions: Res<Assets<QuestDefinition>>,
+    mut log: ResMut<QuestLog>,
+    player_query: Query<(Entity, &Inventory), With<Player>>,
+    trigger_query: Query<(&QuestTrigger, &CollidingEntities)>,
+) {
+    let kills: Vec<&EnemyKilled> = killed.read().collect();
+    let Ok((player, inventory)) = player_query.single() else {
+        return;
+    };
+    let reached: Vec<&str> = trigger_query
+        .iter()
+        .filter(|(_, colliding)| colliding.contains(&player))
+        .map(|(trigger, _)| trigger.0.as_str())
+        .collect();
+
+    // Only flag a change when progress actually moved, every change gets saved
+    let mut changed = false;
+    for quest in &mut log.bypass_change_detection().active {
+        let Some(definition) = library.get(&definitions, &quest.id) else {
+            continue;
+        };
+        quest.progress.resize(definition.objectives.len(), 0);
+        for (objective, progress) in definition.objectives.iter().zip(&mut quest.progress) {
+            let counted = |matches: &dyn Fn(&EnemyKilled) -> bool| {
+                kills.iter().filter(|kill| matches(kill)).count() as u32
+            };
+            let updated = match &objective.goal {
+                Goal::Kill { kind, .. } => *progress + counted(&|kill| kill.kind == Some(*kind)),
+                Goal::KillAny(_) => *progress + kills.len() as u32,
+                Goal::DefeatBoss => *progress + counted(&|kill| kill.boss),
+                Goal::Reach(id) => *progress + reached.contains(&id.as_str()) as u32,
+                // Once collected it stays done, even if the item gets used up
+                Goal::Collect { item, .. } => (*progress).max(inventory.count(item)),
+            }
+            .min(objective.goal.target());
+            if updated != *progress {
+                *progress = updated;
+                changed = true;
+            }
+        }
+    }
+    if changed {
+        log.set_changed();
+    }
+}
+
+fn complete_quests(
+    library: Res<QuestLibrary>,
+    definitions: Res<Assets<QuestDefinition>>,
+    item_library: Res<ItemLibrary>,
+    item_definitions: Res<Assets<ItemDefinition>>,
+    mut log: ResMut<QuestLog>,
+    mut completed: EventWriter<QuestCompleted>,
+    mut drops: EventWriter<DropItems>,
+    mut player_query: Query<(&mut Progression, &mut Inventory, &GlobalTransform), With<Player>>,
+) {
+    if !log.is_changed() {
+        return;
+    }
+    let Ok((mut progression, mut inventory, transform)) = player_query.single_mut() else {
+        return;
+    };
+    let finished: Vec<String> = log
+        .active
+        .iter()
+        .filter(|quest| {
+            library
+                .get(&definitions, &quest.id)
+                .is_some_and(|definition| {
+                    quest.progress.len() == definition.objectives.len()
+                        && definition
+                            .objectives
+                            .iter()
+                            .zip(&quest.progress)
+                            .all(|(objective, progress)| *progress >= objective.goal.target())
+                })
+        })
+        .map(|quest| quest.id.clone())
+        .collect();
+
+    for id in finished {
+        let Some(definition) = library.get(&definitions, &id) else {
+            continue;
+        };
+        progression.add_xp(definition.rewards.xp);
+        for (item, count) in &definition.rewards.items {
+            let Some(item_definition) = item_library.get(&item_definitions, item) else {
+                warn!("Quest {id} rewards unknown item {item}");
+                continue;
+            };
+            // Rewards don't get lost to a full bag, whatever doesn't fit lands at
+            // the player's feet to be picked up once there's room
+            let left = inventory.add(item, item_definition, *count);
+            if left > 0 {
+                drops.write(DropItems {
+                    item: item.clone(),
+                    count: left,
+                    position: transform.translation() + Vec3::Y * 0.5,
+                });
+            }
+        }
+        log.active.retain(|quest| quest.id != id);
+        log.completed.push(id.clone());
+        completed.write(QuestCompleted(id));
+    }
+}
+
+fn save_quest_log(
+    log: Res<QuestLog>,
+    level: Res<CurrentLevel>,
+    mut save: ResMut<SaveData>,
+    mut save_game: EventWriter<SaveGame>,
+) {
+    // Restoring it counts as a change, no need to write that straight back
+    if log.is_changed() && save.quests_by_level.get(&level.0) != Some(&*log) {
+        save.quests_by_level.insert(level.0.clone(), log.clone());
+        save_game.write(SaveGame);
+    }
+}
+
+/// Puts the first unfinished objective of the oldest active quest on the HUD.
+fn show_current_objective(
+    library: Res<QuestLibrary>,
+    definitions: Res<Assets<QuestDefinition>>,
+    log: Res<QuestLog>,
+    mut objective: ResMut<HudObjective>,
+) {
+    if !log.is_changed() && !library.is_changed() {
+        return;
+    }
+    objective.0 = log.active.iter().find_map(|quest| {
+        let definition = library.get(&definitions, &quest.id)?;
+        definition
+            .objectives
+            .iter()
+            .enumerate()
+            .find_map(|(index, objective)| {
+                let progress = quest.progress.get(index).copied().unwrap_or_default();
+                let target = objective.goal.target();
+                (progress < target).then(|| {
+                    if target > 1 {
+                        format!("{} ({progress}/{target})", objective.description)
+                    } else {
+                        objective.description.clone()
+                    }
+                })
+            })
+    });
+}
+
+fn announce_completed_quests(
+    mut completed: EventReader<QuestCompleted>,
+    library: Res<QuestLibrary>,
+    definitions: Res<Assets<QuestDefinition>>,
+) {
+    for QuestCompleted(id) in completed.read() {
+        if let Some(definition) = library.get(&definitions, id) {
+            info!("Completed quest {}", definition.name);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::gameplay::items::{Equipment, EquipmentSlot, ItemDefinition, ItemLibrary};
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Stats>().add_systems(
+            Update,
+            (apply_equipment_modifiers, tick_timed_modifiers).run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+#[derive(Reflect, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stat {
+    WalkSpeed,
+    JumpHeight,
+    DashDistance,
+    FireballDamage,
+    FireballSpeed,
+    /// Multiplier on incoming damage, armor pushes it below 1
+    DamageTaken,
+}
+
+#[derive(Reflect, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Modifier {
+    Add(f32),
+    Multiply(f32),
+}
+
+/// Where a modifier came from, so it can be taken off again.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModifierSource {
+    Equipment(EquipmentSlot),
+    Buff,
+    Level,
+    Skill,
+    /// Slows and the like from status effects, by effect name so each only takes
+    /// off its own
+    Status(&'static str),
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub struct StatModifier {
+    pub stat: Stat,
+    pub modifier: Modifier,
+    pub source: ModifierSource,
+    /// Seconds left, `None` lasts until the source removes it
+    pub remaining: Option<f32>,
+}
+
+/// Base values plus everything currently modifying them.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Stats {
+    pub base: HashMap<Stat, f32>,
+    pub modifiers: Vec<StatModifier>,
+}
+
+impl Stats {
+    pub fn new(base: impl IntoIterator<Item = (Stat, f32)>) -> Self {
+        Self {
+            base: base.into_iter().collect(),
+            modifiers: Vec::new(),
+        }
+    }
+
+    /// Base value with every addition applied, then every multiplier.
+    pub fn get(&self, stat: Stat) -> f32 {
+        let (add, multiply) = self
+            .modifiers
+            .iter()
+            .filter(|modifier| modifier.stat == stat)
+            .fold((0.0, 1.0), |(add, multiply), modifier| {
+                match modifier.modifier {
+                    Modifier::Add(value) => (add + value, multiply),
+                    Modifier::Multiply(value) => (add, multiply * value),
+                }
+            });
+        (self.base.get(&stat).copied().unwrap_or_default() + add) * multiply
+    }
+
+    pub fn add_modifier(
+        &mut self,
+        stat: Stat,
+        modifier: Modifier,
+        source: ModifierSource,
+        duration: Option<f32>,
+    ) {
+        self.modifiers.push(StatModifier {
+            stat,
+            modifier,
+            source,
+            remaining: duration,
+        });
+    }
+}
+
+/// Rebuilds gear modifiers whenever something is (un)equipped.
+fn apply_equipment_modifiers(
+    library: Option<Res<ItemLibrary>>,
+    definitions: Res<Assets<ItemDefinition>>,
+    mut query: Query<(Ref<Equipment>, &mut Stats)>,
+) {
+    let Some(library) = library else {
+        return;
+    };
+    for (equipment, mut stats) in &mut query {
+        // Item definitions can finish loading after the game starts
+        if !equipment.is_changed() && !library.is_changed() {
+            continue;
+        }
+        stats
+            .modifiers
+            .retain(|modifier| !matches!(modifier.source, ModifierSource::Equipment(_)));
+        for (slot, item) in &equipment.slots {
+            let Some(definition) = library.get(&definitions, item) else {
+                continue;
+            };
+            for (stat, modifier) in &definition.modifiers {
+                stats.add_modifier(*stat, *modifier, ModifierSource::Equipment(*slot), None);
+            }
+        }
+    }
+}
+
+fn tick_timed_modifiers(time: Res<Time>, mut query: Query<&mut Stats>) {
+    let dt = time.delta_secs();
+    for mut stats in &mut query {
+        // Don't trip change detection every frame for stats with nothing timed
+        if stats
+            .modifiers
+            .iter()
+            .all(|modifier| modifier.remaining.is_none())
+        {
+            continue;
+        }
+        stats
+            .modifiers
+            .retain_mut(|modifier| match &mut modifier.remaining {
+                Some(remaining) => {
+                    *remaining -= dt;
+                    *remaining > 0.0
+                }
+                None => true,
+            });
+    }
+}
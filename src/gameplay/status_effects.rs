@@ -0,0 +1,518 @@
+use std::marker::PhantomData;
+
+use avian3d::prelude::*;
+use bevy::{color::palettes::css, ecs::component::Mutable, prelude::*};
+
+use crate::GameState;
+use crate::gameplay::elements::{DealDamage, Element};
+use crate::gameplay::enemies::melee_creep::Enemy;
+use crate::gameplay::stats::{Modifier, ModifierSource, Stat, Stats};
+use crate::player::Player;
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StatusVolume>()
+            .add_systems(
+                Update,
+                (
+                    status_volume_system,
+                    (
+                        tick_status::<Burning>,
+                        tick_status::<Poisoned>,
+                        tick_status::<Slowed>,
+                        tick_status::<Stunned>,
+                        tick_status::<Wet>,
+                    ),
+                )
+                    .chain()
+                    .in_set(StatusEffectSet)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    show_indicator::<Burning>,
+                    show_indicator::<Poisoned>,
+                    show_indicator::<Slowed>,
+                    show_indicator::<Stunned>,
+                    show_indicator::<Wet>,
+                )
+                    .after(StatusEffectSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Effects have been ticked and applied once this set is done.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StatusEffectSet;
+
+/// What happens when an effect lands on something that already has it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stacking {
+    /// Keep the current effect, restart its duration
+    Refresh,
+    /// Add a stack (up to `MAX_STACKS`) and restart the duration
+    Stack,
+    /// Throw the current effect away for the new one
+    Replace,
+}
+
+/// Duration and tick bookkeeping shared by every effect.
+#[derive(Clone, Debug, Reflect)]
+pub struct EffectTimer {
+    pub duration: f32,
+    pub remaining: f32,
+    /// Seconds between ticks, 0 for effects that don't tick
+    pub tick_interval: f32,
+    pub stacks: u32,
+    until_tick: f32,
+    /// Ticks that went off this frame
+    ticks: u32,
+}
+
+impl EffectTimer {
+    pub fn new(duration: f32) -> Self {
+        Self::ticking(duration, 0.0)
+    }
+
+    pub fn ticking(duration: f32, tick_interval: f32) -> Self {
+        Self {
+            duration,
+            remaining: duration,
+            tick_interval,
+            stacks: 1,
+            until_tick: tick_interval,
+            ticks: 0,
+        }
+    }
+
+    /// Whether a tick went off this frame
+    pub fn ticked(&self) -> bool {
+        self.ticks > 0
+    }
+}
+
+pub trait StatusEffect: Component<Mutability = Mutable> + Clone {
+    const STACKING: Stacking;
+    const MAX_STACKS: u32 = 1;
+    /// Colour of the marker floating over affected entities
+    const COLOR: Srgba;
+    /// Where the marker sits in the row, so several effects can show at once
+    const INDICATOR_SLOT: u32;
+
+    fn timer(&self) -> &EffectTimer;
+    fn timer_mut(&mut self) -> &mut EffectTimer;
+
+    /// Every time the effect lands, refreshes, stacks and replacements included
+    fn on_apply(&self, _entity: Entity, _commands: &mut Commands) {}
+
+    /// Once a frame when any ticks went off, `ticks` says how many
+    fn on_tick(&self, _entity: Entity, _ticks: u32, _commands: &mut Commands) {}
+
+    /// When the duration runs out, just before the effect is removed
+    fn on_expire(&self, _entity: Entity, _commands: &mut Commands) {}
+}
+
+/// Hurts for every tick and stack, for damage over time effects.
+fn deal_tick_damage(
+    commands: &mut Commands,
+    target: Entity,
+    timer: &EffectTimer,
+    damage_per_tick: f32,
+    ticks: u32,
+    element: Element,
+) {
+    commands.send_event(DealDamage {
+        target,
+        amount: damage_per_tick * (ticks * timer.stacks) as f32,
+        element,
+    });
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Burning {
+    pub timer: EffectTimer,
+    /// Per stack
+    pub damage_per_tick: f32,
+}
+
+impl Burning {
+    pub fn new(damage_per_second: f32, duration: f32) -> Self {
+        Self {
+            timer: EffectTimer::ticking(duration, 0.5),
+            damage_per_tick: damage_per_second * 0.5,
+        }
+    }
+}
+
+impl StatusEffect for Burning {
+    const STACKING: Stacking = Stacking::Stack;
+    const MAX_STACKS: u32 = 3;
+    const COLOR: Srgba = css::ORANGE_RED;
+    const INDICATOR_SLOT: u32 = 0;
+
+    fn timer(&self) -> &EffectTimer {
+        &self.timer
+    }
+
+    fn timer_mut(&mut self) -> &mut EffectTimer {
+        &mut self.timer
+    }
+
+    fn on_tick(&self, entity: Entity, ticks: u32, commands: &mut Commands) {
+        deal_tick_damage(
+            commands,
+            entity,
+            &self.timer,
+            self.damage_per_tick,
+            ticks,
+            Element::Fire,
+        );
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Poisoned {
+    pub timer: EffectTimer,
+    /// Per stack
+    pub damage_per_tick: f32,
+}
+
+impl Poisoned {
+    pub fn new(damage_per_second: f32, duration: f32) -> Self {
+        Self {
+            timer: EffectTimer::ticking(duration, 1.0),
+            damage_per_tick: damage_per_second,
+        }
+    }
+}
+
+impl StatusEffect for Poisoned {
+    const STACKING: Stacking = Stacking::Stack;
+    const MAX_STACKS: u32 = 5;
+    const COLOR: Srgba = css::LIMEGREEN;
+    const INDICATOR_SLOT: u32 = 1;
+
+    fn timer(&self) -> &EffectTimer {
+        &self.timer
+    }
+
+    fn timer_mut(&mut self) -> &mut EffectTimer {
+        &mut self.timer
+    }
+
+    fn on_tick(&self, entity: Entity, ticks: u32, commands: &mut Commands) {
+        deal_tick_damage(
+            commands,
+            entity,
+            &self.timer,
+            self.damage_per_tick,
+            ticks,
+            Element::Poison,
+        );
+    }
+}
+
+/// Scales movement speed while it lasts.
+#[derive(Component, Clone, Debug)]
+pub struct Slowed {
+    pub timer: EffectTimer,
+    /// 0.5 is half speed
+    pub factor: f32,
+}
+
+impl Slowed {
+    pub fn new(factor: f32, duration: f32) -> Self {
+        Self {
+            timer: EffectTimer::new(duration),
+            factor,
+        }
+    }
+}
+
+/// Tags the slow's own stat modifiers
+const SLOWED: &str = "slowed";
+
+impl StatusEffect for Slowed {
+    const STACKING: Stacking = Stacking::Replace;
+    const COLOR: Srgba = css::LIGHT_BLUE;
+    const INDICATOR_SLOT: u32 = 2;
+
+    fn timer(&self) -> &EffectTimer {
+        &self.timer
+    }
+
+    fn timer_mut(&mut self) -> &mut EffectTimer {
+        &mut self.timer
+    }
+
+    /// Enemies read the factor in their movement, the player goes through stats
+    fn on_apply(&self, entity: Entity, commands: &mut Commands) {
+        let modifier = Modifier::Multiply(self.factor);
+        commands
+            .entity(entity)
+            .queue(move |mut entity: EntityWorldMut| {
+                if let Some(mut stats) = entity.get_mut::<Stats>() {
+                    clear_status_modifiers(&mut stats, SLOWED);
+                    stats.add_modifier(
+                        Stat::WalkSpeed,
+                        modifier,
+                        ModifierSource::Status(SLOWED),
+                        None,
+                    );
+                }
+            });
+    }
+
+    fn on_expire(&self, entity: Entity, commands: &mut Commands) {
+        commands.entity(entity).queue(|mut entity: EntityWorldMut| {
+            if let Some(mut stats) = entity.get_mut::<Stats>() {
+                clear_status_modifiers(&mut stats, SLOWED);
+            }
+        });
+    }
+}
+
+/// No moving, attacking or thinking while it lasts.
+#[derive(Component, Clone, Debug)]
+pub struct Stunned {
+    pub timer: EffectTimer,
+}
+
+impl Stunned {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            timer: EffectTimer::new(duration),
+        }
+    }
+}
+
+impl StatusEffect for Stunned {
+    const STACKING: Stacking = Stacking::Refresh;
+    const COLOR: Srgba = css::YELLOW;
+    const INDICATOR_SLOT: u32 = 3;
+
+    fn timer(&self) -> &EffectTimer {
+        &self.timer
+    }
+
+    fn timer_mut(&mut self) -> &mut EffectTimer {
+        &mut self.timer
+    }
+}
+
+fn clear_status_modifiers(stats: &mut Stats, effect: &'static str) {
+    stats
+        .modifiers
+        .retain(|modifier| modifier.source != ModifierSource::Status(effect));
+}
+
+/// Soaked from swimming. Puts out fire and conducts lightning.
+#[derive(Component, Clone, Debug)]
+pub struct Wet {
+    pub timer: EffectTimer,
+}
+
+impl Default for Wet {
+    fn default() -> Self {
+        Self {
+            timer: EffectTimer::new(5.0),
+        }
+    }
+}
+
+impl StatusEffect for Wet {
+    const STACKING: Stacking = Stacking::Refresh;
+    const COLOR: Srgba = css::DODGER_BLUE;
+    const INDICATOR_SLOT: u32 = 4;
+
+    fn timer(&self) -> &EffectTimer {
+        &self.timer
+    }
+
+    fn timer_mut(&mut self) -> &mut EffectTimer {
+        &mut self.timer
+    }
+}
+
+pub trait ApplyStatusExt {
+    /// Adds an effect, or merges it with the one already there according to its `Stacking`.
+    fn apply_status<T: StatusEffect>(&mut self, effect: T) -> &mut Self;
+}
+
+impl ApplyStatusExt for EntityCommands<'_> {
+    fn apply_status<T: StatusEffect>(&mut self, effect: T) -> &mut Self {
+        self.queue(move |mut entity: EntityWorldMut| {
+            let Some(mut existing) = entity.get_mut::<T>() else {
+                entity.insert(effect);
+                return;
+            };
+            match T::STACKING {
+                Stacking::Refresh => {
+                    let timer = existing.timer_mut();
+                    timer.remaining = timer.remaining.max(effect.timer().duration);
+                }
+                Stacking::Stack => {
+                    let timer = existing.timer_mut();
+                    timer.stacks = (timer.stacks + 1).min(T::MAX_STACKS);
+                    timer.remaining = timer.remaining.max(effect.timer().duration);
+                }
+                Stacking::Replace => *existing = effect,
+            }
+        })
+    }
+}
+
+/// Which effect a `StatusVolume` puts on things.
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq)]
+pub enum StatusKind {
+    #[default]
+    Burning,
+    Poisoned,
+    Slowed,
+    Stunned,
+}
+
+/// Hazard authored in Blender (poison swamp, sticky tar...). Keeps applying its effect
+/// to anything standing in it. Needs a collider.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[require(Sensor, CollidingEntities)]
+pub struct StatusVolume {
+    pub kind: StatusKind,
+    /// Damage per second for burning/poison, speed factor for slows
+    pub magnitude: f32,
+    pub duration: f32,
+    /// Seconds between re-applications, so stacks build up at a sensible rate
+    pub interval: f32,
+    #[reflect(ignore)]
+    pub cooldown: f32,
+}
+
+impl Default for StatusVolume {
+    fn default() -> Self {
+        Self {
+            kind: StatusKind::Poisoned,
+            magnitude: 4.0,
+            duration: 5.0,
+            interval: 1.0,
+            cooldown: 0.0,
+        }
+    }
+}
+
+fn status_volume_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut volume_query: Query<(&mut StatusVolume, &CollidingEntities)>,
+    target_query: Query<(), Or<(With<Player>, With<Enemy>)>>,
+) {
+    for (mut volume, colliding) in &mut volume_query {
+        volume.cooldown -= time.delta_secs();
+        if volume.cooldown > 0.0 {
+            continue;
+        }
+        volume.cooldown = volume.interval;
+        for entity in colliding
+            .iter()
+            .filter(|entity| target_query.contains(**entity))
+        {
+            let mut target = commands.entity(*entity);
+            match volume.kind {
+                StatusKind::Burning => {
+                    target.apply_status(Burning::new(volume.magnitude, volume.duration));
+                }
+                StatusKind::Poisoned => {
+                    target.apply_status(Poisoned::new(volume.magnitude, volume.duration));
+                }
+                StatusKind::Slowed => {
+                    target.apply_status(Slowed::new(volume.magnitude, volume.duration));
+                }
+                StatusKind::Stunned => {
+                    target.apply_status(Stunned::new(volume.duration));
+                }
+            }
+        }
+    }
+}
+
+fn tick_status<T: StatusEffect>(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut T)>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut effect) in &mut query {
+        // Changed since last frame means apply_status just put it on or topped it up
+        if effect.is_changed() {
+            effect.on_apply(entity, &mut commands);
+        }
+        let timer = effect.timer_mut();
+        timer.remaining -= dt;
+        timer.ticks = 0;
+        if timer.tick_interval > 0.0 {
+            timer.until_tick -= dt;
+            while timer.until_tick <= 0.0 {
+                timer.until_tick += timer.tick_interval;
+                timer.ticks += 1;
+            }
+        }
+        let (ticks, expired) = (timer.ticks, timer.remaining <= 0.0);
+        // Ticks from the last frame still land, the effect goes after that
+        if ticks > 0 {
+            effect.on_tick(entity, ticks, &mut commands);
+        }
+        if expired {
+            effect.on_expire(entity, &mut commands);
+            commands.entity(entity).remove::<T>();
+        }
+    }
+}
+
+/// Marker sphere floating over something with effect `T`.
+#[derive(Component)]
+struct StatusIndicator<T: StatusEffect>(PhantomData<T>);
+
+fn show_indicator<T: StatusEffect>(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut assets: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+    added: Query<Entity, Added<T>>,
+    mut removed: RemovedComponents<T>,
+    children_query: Query<&Children>,
+    indicator_query: Query<(), With<StatusIndicator<T>>>,
+) {
+    let (mesh, material) = assets.get_or_insert_with(|| {
+        (
+            meshes.add(Sphere { radius: 0.15 }),
+            materials.add(StandardMaterial {
+                base_color: T::COLOR.into(),
+                unlit: true,
+                ..default()
+            }),
+        )
+    });
+    for entity in &added {
+        commands.entity(entity).with_child((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_xyz(T::INDICATOR_SLOT as f32 * 0.35 - 0.5, 1.8, 0.0),
+            StatusIndicator::<T>(PhantomData),
+        ));
+    }
+    for entity in removed.read() {
+        let Ok(children) = children_query.get(entity) else {
+            continue;
+        };
+        for child in children
+            .iter()
+            .filter(|child| indicator_query.contains(*child))
+        {
+            commands.entity(child).despawn();
+        }
+    }
+}
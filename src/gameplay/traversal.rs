@@ -0,0 +1,418 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use bevy_tnua::{
+    builtins::{TnuaBuiltinDash, TnuaBuiltinWallSlide},
+    control_helpers::TnuaSimpleAirActionsCounter,
+    prelude::*,
+};
+
+use crate::GameState;
+use crate::gameplay::dialogue::ActiveDialogue;
+use crate::gameplay::stats::{Stat, Stats};
+use crate::gameplay::status_effects::Stunned;
+use crate::gameplay::vitals::Stamina;
+use crate::gameplay::water::swim_controls;
+use crate::player::Player;
+
+/// Tnua action names, so our own jumps and dashes can be told apart from knockbacks
+pub(crate) const DASH: &str = "dash";
+const WALL_JUMP: &str = "wall_jump";
+const LEDGE_CLIMB: &str = "ledge_climb";
+
+/// How far from the capsule's surface a wall still counts as touching
+const WALL_REACH: f32 = 0.35;
+/// Stamina each dash costs
+pub(crate) const DASH_STAMINA_COST: f32 = 25.0;
+
+pub struct TraversalPlugin;
+
+impl Plugin for TraversalPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TraversalAbilities>()
+            .add_event::<DashStarted>()
+            .add_systems(
+                FixedUpdate,
+                (detect_walls_and_ledges, traversal_controls)
+                    .chain()
+                    // Reads the walk basis apply_controls just fed, and stays out of the
+                    // way once swim_controls has swapped it out
+                    .after(swim_controls)
+                    .in_set(TnuaUserControlsSystemSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// The player set off on a dash.
+#[derive(Event)]
+pub struct DashStarted(pub Entity);
+
+/// Which movement tricks the player has unlocked. Progression flips these on.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[require(TnuaSimpleAirActionsCounter, DashCharges, WallContact, Traversing)]
+pub struct TraversalAbilities {
+    /// Extra jumps allowed in the air, 0 means no double jump
+    pub air_jumps: usize,
+    pub wall_jump: bool,
+    pub ledge_grab: bool,
+    pub air_dash: bool,
+    pub dash_charges: u32,
+}
+
+impl Default for TraversalAbilities {
+    fn default() -> Self {
+        Self {
+            air_jumps: 0,
+            wall_jump: false,
+            ledge_grab: false,
+            air_dash: false,
+            dash_charges: 1,
+        }
+    }
+}
+
+impl TraversalAbilities {
+    /// Everything unlocked, handy for testing levels
+    pub fn all() -> Self {
+        Self {
+            air_jumps: 1,
+            wall_jump: true,
+            ledge_grab: true,
+            air_dash: true,
+            dash_charges: 2,
+        }
+    }
+}
+
+/// Dashes left before touching the ground again
+#[derive(Component, Default)]
+pub struct DashCharges(pub u32);
+
+/// Filled in by the shape casts every tick.
+#[derive(Component, Default)]
+pub struct WallContact {
+    pub wall: Option<WallHit>,
+    /// Top of a ledge within grabbing distance
+    pub ledge: Option<Vec3>,
+}
+
+#[derive(Clone, Copy)]
+pub struct WallHit {
+    pub entity: Entity,
+    pub point: Vec3,
+    pub normal: Dir3,
+}
+
+/// What special movement the player is in the middle of.
+#[derive(Component, Default, Clone, Copy, PartialEq)]
+pub enum Traversing {
+    #[default]
+    None,
+    WallSlide,
+    Hanging {
+        ledge: Vec3,
+        normal: Dir3,
+    },
+    /// Steering away from the wall (or onto the ledge) for a moment after jumping off it
+    Leaving {
+        direction: Vec3,
+        remaining: f32,
+    },
+}
+
+fn detect_walls_and_ledges(
+    spatial_query: SpatialQuery,
+    sensor_query: Query<(), With<Sensor>>,
+    mut player_query: Query<
+        (
+            Entity,
+            &Transform,
+            &TnuaController,
+            &TraversalAbilities,
+            &mut WallContact,
+        ),
+        With<Player>,
+    >,
+) {
+    for (entity, transform, controller, abilities, mut contact) in &mut player_query {
+        contact.wall = None;
+        contact.ledge = None;
+        if !(abilities.wall_jump || abilities.ledge_grab)
+            || !controller.is_airborne().unwrap_or(false)
+        {
+            continue;
+        }
+
+        // Look where the player is trying to go, or straight ahead if they're not pushing
+        let desired = controller
+            .concrete_basis::<TnuaBuiltinWalk>()
+            .map(|(walk, _)| walk.desired_velocity)
+            .unwrap_or_default();
+        let Ok(direction) = Dir3::new(Vec3::new(desired.x, 0.0, desired.z))
+            .or_else(|_| Dir3::new(transform.forward().with_y(0.0)))
+        else {
+            continue;
+        };
+
+        let filter = SpatialQueryFilter::from_excluded_entities([entity]);
+        let not_sensor = |e: Entity| !sensor_query.contains(e);
+        let origin = transform.translation;
+
+        let Some(hit) = spatial_query.cast_shape_predicate(
+            &Collider::sphere(0.3),
+            origin,
+            Quat::IDENTITY,
+            direction,
+            &ShapeCastConfig::from_max_distance(0.2 + WALL_REACH),
+            &filter,
+            &not_sensor,
+        ) else {
+            continue;
+        };
+        // Only steep surfaces are walls
+        let Ok(normal) = Dir3::new(hit.normal1) else {
+            continue;
+        };
+        if normal.y.abs() > 0.3 {
+            continue;
+        }
+        contact.wall = Some(WallHit {
+            entity: hit.entity,
+            point: hit.point1,
+            normal,
+        });
+
+        // A ledge is a wall at chest height with nothing at head height, and floor on top
+        let head = origin + Vec3::Y * 1.2;
+        let head_blocked = spatial_query
+            .cast_ray_predicate(head, direction, 1.0, true, &filter, &not_sensor)
+            .is_some();
+        if head_blocked {
+            continue;
+        }
+        let above_ledge = head + *direction * (hit.distance + 0.6);
+        if let Some(top) = spatial_query.cast_ray_predicate(
+            above_ledge,
+            Dir3::NEG_Y,
+            1.0,
+            true,
+            &filter,
+            &not_sensor,
+        ) {
+            contact.ledge = Some(above_ledge - Vec3::Y * top.distance);
+        }
+    }
+}
+
+fn traversal_controls(
+    time: Res<Time>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    dialogue: Res<ActiveDialogue>,
+    mut dashed: EventWriter<DashStarted>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut TnuaController,
+            &mut TnuaSimpleAirActionsCounter,
+            &TraversalAbilities,
+            &mut DashCharges,
+            &WallContact,
+            &mut Traversing,
+            &Transform,
+            &LinearVelocity,
+            &Stats,
+            &mut Stamina,
+        ),
+        (With<Player>, Without<Stunned>),
+    >,
+) {
+    // No jumping off mid-sentence
+    if dialogue.0.is_some() {
+        return;
+    }
+    let gamepad = gamepads.iter().next();
+    let jump_held = keyboard.pressed(KeyCode::Space)
+        || gamepad.is_some_and(|pad| pad.pressed(GamepadButton::South));
+    let jump_pressed = keyboard.just_pressed(KeyCode::Space)
+        || gamepad.is_some_and(|pad| pad.just_pressed(GamepadButton::South));
+    let dash_held = keyboard.pressed(KeyCode::ShiftLeft)
+        || gamepad.is_some_and(|pad| pad.pressed(GamepadButton::RightTrigger));
+    let drop_pressed = keyboard.just_pressed(KeyCode::ControlLeft)
+        || gamepad.is_some_and(|pad| pad.just_pressed(GamepadButton::East));
+
+    for (
+        entity,
+        mut controller,
+        mut air_counter,
+        abilities,
+        mut charges,
+        contact,
+        mut traversing,
+        transform,
+        velocity,
+        stats,
+        mut stamina,
+    ) in &mut player_query
+    {
+        air_counter.update(&controller);
+        let airborne = controller.is_airborne().unwrap_or(false);
+        let Some(walk) = controller
+            .concrete_basis::<TnuaBuiltinWalk>()
+            .map(|(walk, _)| walk.clone())
+        else {
+            continue;
+        };
+        let input = walk.desired_velocity.with_y(0.0).normalize_or_zero();
+        let jump_height = stats.get(Stat::JumpHeight);
+
+        // --- DASH CHARGES ---
+        // Back on the ground with no dash running refills everything
+        if !airborne && controller.action_name() != Some(DASH) {
+            charges.0 = abilities.dash_charges;
+        }
+        if controller.action_flow_status().just_starting() == Some(DASH) {
+            charges.0 = charges.0.saturating_sub(1);
+            // Only paid once Tnua has actually let the dash go
+            stamina.0.try_spend(DASH_STAMINA_COST);
+            dashed.write(DashStarted(entity));
+        }
+
+        *traversing = match *traversing {
+            _ if !airborne => Traversing::None,
+            Traversing::Leaving {
+                direction,
+                remaining,
+            } if remaining > 0.0 => Traversing::Leaving {
+                direction,
+                remaining: remaining - time.delta_secs(),
+            },
+            Traversing::Hanging { .. } if drop_pressed => Traversing::None,
+            hanging @ Traversing::Hanging { .. } => hanging,
+            _ => match (contact.ledge, contact.wall) {
+                // Grab ledges on the way down or near the top of a jump
+                (Some(ledge), Some(wall)) if abilities.ledge_grab && velocity.y < 1.0 => {
+                    Traversing::Hanging {
+                        ledge,
+                        normal: wall.normal,
+                    }
+                }
+                (_, Some(wall))
+                    if abilities.wall_jump
+                        && velocity.y < 0.0
+                        && input.dot(*wall.normal) < -0.3 =>
+                {
+                    Traversing::WallSlide
+                }
+                _ => Traversing::None,
+            },
+        };
+
+        match *traversing {
+            Traversing::Hanging { ledge, normal } => {
+                if jump_pressed {
+                    // Pop up past the lip, then walk onto it
+                    let height = (ledge.y - (transform.translation.y - 1.5)).max(0.5) + 0.5;
+                    controller.named_action(
+                        LEDGE_CLIMB,
+                        TnuaBuiltinJump {
+                            height,
+                            allow_in_air: true,
+                            ..Default::default()
+                        },
+                    );
+                    *traversing = Traversing::Leaving {
+                        direction: -*normal * 4.0,
+                        remaining: 0.5,
+                    };
+                } else {
+                    // A wall slide that never slides is a hang
+                    controller.action(TnuaBuiltinWallSlide {
+                        wall_entity: contact.wall.map(|wall| wall.entity),
+                        contact_point_with_wall: contact
+                            .wall
+                            .map(|wall| wall.point)
+                            .unwrap_or(transform.translation - *normal * 0.5),
+                        normal,
+                        force_forward: Some(-normal),
+                        max_fall_speed: 0.0,
+                        maintain_distance: Some(0.5),
+                        max_sideways_speed: 0.0,
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+            Traversing::WallSlide => {
+                let Some(wall) = contact.wall else {
+                    continue;
+                };
+                if jump_pressed {
+                    controller.named_action(
+                        WALL_JUMP,
+                        TnuaBuiltinJump {
+                            height: jump_height,
+                            allow_in_air: true,
+                            ..Default::default()
+                        },
+                    );
+                    // Kicking off a wall gives the double jump back
+                    air_counter.reset_count();
+                    *traversing = Traversing::Leaving {
+                        direction: *wall.normal * 10.0,
+                        remaining: 0.3,
+                    };
+                } else {
+                    controller.action(TnuaBuiltinWallSlide {
+                        wall_entity: Some(wall.entity),
+                        contact_point_with_wall: wall.point,
+                        normal: wall.normal,
+                        force_forward: Some(-wall.normal),
+                        maintain_distance: Some(0.5),
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+            Traversing::Leaving { direction, .. } => {
+                // Ignore the stick until we're clear of the wall
+                controller.basis(TnuaBuiltinWalk {
+                    desired_velocity: direction,
+                    ..walk
+                });
+                // Keep feeding whichever jump started this
+                if jump_held && matches!(controller.action_name(), Some(WALL_JUMP | LEDGE_CLIMB)) {
+                    controller.prolong_action();
+                }
+                continue;
+            }
+            Traversing::None => {}
+        }
+
+        // --- JUMP ---
+        if jump_held {
+            controller.action(TnuaBuiltinJump {
+                height: jump_height,
+                // Walking off a ledge counts as the first air action, like jumping
+                allow_in_air: air_counter.air_count_for(TnuaBuiltinJump::NAME)
+                    <= abilities.air_jumps,
+                ..Default::default()
+            });
+        }
+
+        //---DASH
+        let dashing = controller.action_name() == Some(DASH);
+        if dash_held && (dashing || (charges.0 > 0 && stamina.0.current >= DASH_STAMINA_COST)) {
+            controller.named_action(
+                DASH,
+                TnuaBuiltinDash {
+                    displacement: input * stats.get(Stat::DashDistance),
+                    desired_forward: walk.desired_forward,
+                    allow_in_air: abilities.air_dash,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}
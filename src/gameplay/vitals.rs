@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+
+pub struct VitalsPlugin;
+
+impl Plugin for VitalsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<(Mana, Stamina)>().add_systems(
+            Update,
+            (regenerate::<Mana>, regenerate::<Stamina>).run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Something spent on abilities that fills back up over time.
+#[derive(Reflect, Clone, Copy, Debug)]
+pub struct Pool {
+    pub current: f32,
+    pub max: f32,
+    /// Per second
+    pub regen: f32,
+    /// Seconds after spending before it starts filling again
+    pub regen_delay: f32,
+    /// Time left on the current delay
+    pub delay_remaining: f32,
+}
+
+impl Pool {
+    pub fn new(max: f32, regen: f32, regen_delay: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            regen,
+            regen_delay,
+            delay_remaining: 0.0,
+        }
+    }
+
+    /// Takes `amount` if there's enough of it.
+    pub fn try_spend(&mut self, amount: f32) -> bool {
+        if self.current < amount {
+            return false;
+        }
+        self.current -= amount;
+        self.delay_remaining = self.regen_delay;
+        true
+    }
+}
+
+/// Paid for spells.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Mana(pub Pool);
+
+impl Default for Mana {
+    fn default() -> Self {
+        Self(Pool::new(100.0, 8.0, 1.0))
+    }
+}
+
+/// Paid for dashes.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Stamina(pub Pool);
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Self(Pool::new(100.0, 30.0, 0.6))
+    }
+}
+
+trait HasPool: Component<Mutability = bevy::ecs::component::Mutable> {
+    fn pool_mut(&mut self) -> &mut Pool;
+}
+
+impl HasPool for Mana {
+    fn pool_mut(&mut self) -> &mut Pool {
+        &mut self.0
+    }
+}
+
+impl HasPool for Stamina {
+    fn pool_mut(&mut self) -> &mut Pool {
+        &mut self.0
+    }
+}
+
+fn regenerate<T: HasPool>(time: Res<Time>, mut query: Query<&mut T>) {
+    let dt = time.delta_secs();
+    for mut pool in &mut query {
+        let pool = pool.pool_mut();
+        if pool.delay_remaining > 0.0 {
+            pool.delay_remaining -= dt;
+        } else if pool.current < pool.max {
+            pool.current = (pool.current + pool.regen * dt).min(pool.max);
+        }
+    }
+}
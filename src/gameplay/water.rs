@@ -0,0 +1,288 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use bevy_tnua::{
+    TnuaBasis, TnuaBasisContext, TnuaMotor, TnuaVelChange,
+    math::{Float, Vector3},
+    prelude::*,
+    util::calc_angular_velchange_to_force_forward,
+};
+
+use crate::GameState;
+use crate::gameplay::elements::{DealDamage, Element};
+use crate::gameplay::enemies::melee_creep::{Enemy, melee_creep_movement_system};
+use crate::player::{Player, apply_controls};
+
+/// Depth (surface to body centre) at which something starts swimming
+const SWIM_ENTER_DEPTH: f32 = 0.5;
+/// Depth below which a swimmer climbs out again, lower than entering so we don't flicker
+const SWIM_EXIT_DEPTH: f32 = 0.2;
+/// Depth at which buoyancy is at full strength
+const FULL_BUOYANCY_DEPTH: f32 = 1.2;
+/// Depth at which the player's head goes under
+const HEAD_DEPTH: f32 = 1.1;
+const SWIM_SPEED: f32 = 5.0;
+/// Upward speed when kicking out of the water
+const SURFACE_JUMP_SPEED: f32 = 7.0;
+const DROWN_DAMAGE_PER_SECOND: f32 = 10.0;
+const DROWN_TICK_SECONDS: f32 = 1.0;
+
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<(WaterVolume, Breath)>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    detect_swimmers.before(apply_controls),
+                    swim_controls
+                        .after(apply_controls)
+                        .in_set(TnuaUserControlsSystemSet),
+                    update_breath,
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                enemy_swim_system
+                    .after(melee_creep_movement_system)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Body of water authored in Blender. The top of its collider is the surface.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[require(Sensor, CollidingEntities)]
+pub struct WaterVolume {
+    /// 1.0 is neutral, higher floats swimmers up to the surface
+    pub buoyancy: f32,
+    /// How quickly swimmers reach the speed they're asking for (1/s)
+    pub drag: f32,
+}
+
+impl Default for WaterVolume {
+    fn default() -> Self {
+        Self {
+            buoyancy: 1.25,
+            drag: 3.0,
+        }
+    }
+}
+
+/// Present while an entity is in swim mode.
+#[derive(Component, Clone, Copy)]
+pub struct Swimming {
+    pub surface: f32,
+    pub depth: f32,
+    pub buoyancy: f32,
+    pub drag: f32,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Breath {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Breath {
+    fn default() -> Self {
+        Self {
+            current: 10.0,
+            max: 10.0,
+        }
+    }
+}
+
+/// Tnua basis used instead of `TnuaBuiltinWalk` while swimming. Moves freely in 3D,
+/// floats toward the surface and slows down in the water.
+#[derive(Clone, Debug)]
+pub struct TnuaSwim {
+    pub desired_velocity: Vector3,
+    pub desired_forward: Option<Dir3>,
+    pub surface_height: Float,
+    pub buoyancy: Float,
+    pub drag: Float,
+}
+
+#[derive(Default)]
+pub struct TnuaSwimState {
+    velocity: Vector3,
+}
+
+impl TnuaBasis for TnuaSwim {
+    const NAME: &'static str = "TnuaSwim";
+    type State = TnuaSwimState;
+
+    fn apply(&self, state: &mut Self::State, ctx: TnuaBasisContext, motor: &mut TnuaMotor) {
+        let depth = self.surface_height - ctx.tracker.translation.y;
+        // Less of the body is in the water near the surface, so it pushes up less
+        let submersion = (depth / FULL_BUOYANCY_DEPTH).clamp(0.0, 1.0);
+        let float = -ctx.tracker.gravity * self.buoyancy * submersion;
+        let steer = (self.desired_velocity - ctx.tracker.velocity) * self.drag;
+        motor.lin = TnuaVelChange::acceleration(float + steer);
+
+        motor.ang = match self.desired_forward {
+            Some(forward) => calc_angular_velchange_to_force_forward(
+                forward,
+                ctx.tracker.rotation,
+                ctx.tracker.angvel,
+                ctx.up_direction,
+                ctx.frame_duration,
+            ),
+            None => TnuaVelChange::ZERO,
+        };
+        state.velocity = ctx.tracker.velocity;
+    }
+
+    fn proximity_sensor_cast_range(&self, _state: &Self::State) -> Float {
+        0.0
+    }
+
+    fn displacement(&self, _state: &Self::State) -> Option<Vector3> {
+        None
+    }
+
+    fn effective_velocity(&self, state: &Self::State) -> Vector3 {
+        state.velocity
+    }
+
+    fn vertical_velocity(&self, state: &Self::State) -> Float {
+        state.velocity.y
+    }
+
+    fn neutralize(&mut self) {
+        self.desired_velocity = Vector3::ZERO;
+        self.desired_forward = None;
+    }
+
+    // Not standing on anything, so nothing that needs ground will trigger
+    fn is_airborne(&self, _state: &Self::State) -> bool {
+        true
+    }
+
+    fn violate_coyote_time(&self, _state: &mut Self::State) {}
+}
+
+fn detect_swimmers(
+    mut commands: Commands,
+    water_query: Query<(&WaterVolume, &CollidingEntities, &ColliderAabb)>,
+    swimmer_query: Query<(Entity, &Transform, Option<&Swimming>), Or<(With<Player>, With<Enemy>)>>,
+) {
+    for (entity, transform, swimming) in &swimmer_query {
+        // Deepest water this entity is touching
+        let water = water_query
+            .iter()
+            .filter(|(_, colliding, _)| colliding.contains(&entity))
+            .map(|(volume, _, aabb)| Swimming {
+                surface: aabb.max.y,
+                depth: aabb.max.y - transform.translation.y,
+                buoyancy: volume.buoyancy,
+                drag: volume.drag,
+            })
+            .max_by(|a, b| a.depth.total_cmp(&b.depth));
+
+        let threshold = if swimming.is_some() {
+            SWIM_EXIT_DEPTH
+        } else {
+            SWIM_ENTER_DEPTH
+        };
+        match water.filter(|water| water.depth > threshold) {
+            Some(water) => {
+                commands.entity(entity).insert(water);
+            }
+            None if swimming.is_some() => {
+                commands.entity(entity).remove::<Swimming>();
+            }
+            None => {}
+        }
+    }
+}
+
+pub(crate) fn swim_controls(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut player_query: Query<(&mut TnuaController, &Swimming), With<Player>>,
+) {
+    let gamepad = gamepads.iter().next();
+    let up = keyboard.pressed(KeyCode::Space)
+        || gamepad.is_some_and(|pad| pad.pressed(GamepadButton::South));
+    let down = keyboard.pressed(KeyCode::ControlLeft)
+        || gamepad.is_some_and(|pad| pad.pressed(GamepadButton::East));
+
+    for (mut controller, swimming) in &mut player_query {
+        // apply_controls has already fed the walk basis, steal its input
+        let (horizontal, desired_forward) = controller
+            .concrete_basis::<TnuaBuiltinWalk>()
+            .map(|(walk, _)| (walk.desired_velocity, walk.desired_forward))
+            .unwrap_or_default();
+
+        let mut desired_velocity = horizontal.clamp_length_max(1.0) * SWIM_SPEED;
+        if up {
+            // Kick hard at the surface so the player can climb out onto the bank
+            desired_velocity.y = if swimming.depth < FULL_BUOYANCY_DEPTH {
+                SURFACE_JUMP_SPEED
+            } else {
+                SWIM_SPEED
+            };
+        } else if down {
+            desired_velocity.y = -SWIM_SPEED;
+        }
+
+        controller.basis(TnuaSwim {
+            desired_velocity,
+            desired_forward,
+            surface_height: swimming.surface,
+            buoyancy: swimming.buoyancy,
+            drag: swimming.drag,
+        });
+    }
+}
+
+fn update_breath(
+    time: Res<Time>,
+    mut damage: EventWriter<DealDamage>,
+    mut until_drown_tick: Local<f32>,
+    mut player_query: Query<(Entity, &mut Breath, Option<&Swimming>), With<Player>>,
+) {
+    let dt = time.delta_secs();
+    for (player, mut breath, swimming) in &mut player_query {
+        let underwater = swimming.is_some_and(|swimming| swimming.depth > HEAD_DEPTH);
+        if underwater {
+            breath.current = (breath.current - dt).max(0.0);
+            if breath.current > 0.0 {
+                *until_drown_tick = 0.0;
+                continue;
+            }
+            // Hurts once a second rather than a sliver every frame
+            *until_drown_tick -= dt;
+            if *until_drown_tick <= 0.0 {
+                *until_drown_tick += DROWN_TICK_SECONDS;
+                damage.write(DealDamage {
+                    target: player,
+                    amount: DROWN_DAMAGE_PER_SECOND * DROWN_TICK_SECONDS,
+                    element: Element::Physical,
+                });
+            }
+        } else {
+            // Catch your breath a lot faster than you lose it
+            breath.current = (breath.current + dt * 4.0).min(breath.max);
+        }
+    }
+}
+
+fn enemy_swim_system(
+    mut enemy_query: Query<(&Swimming, &Transform, &mut LinearVelocity), With<Enemy>>,
+) {
+    for (swimming, transform, mut velocity) in &mut enemy_query {
+        // Enemies don't dive, they paddle along with their head above the water
+        let float_depth = FULL_BUOYANCY_DEPTH / swimming.buoyancy.max(0.01);
+        let depth = swimming.surface - transform.translation.y;
+        let slowdown = 1.0 / (1.0 + swimming.drag * 0.25);
+        velocity.x *= slowdown;
+        velocity.z *= slowdown;
+        velocity.y = (depth - float_depth) * swimming.drag;
+    }
+}
@@ -0,0 +1,322 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    color::palettes::css,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::GameState;
+use crate::camera::ThirdPersonCamera;
+use crate::gameplay::elements::DamageDealt;
+use crate::player::{Health, MaxHealth, Player};
+
+/// Damage numbers drift up this fast, in metres per second
+const NUMBER_RISE_SPEED: f32 = 1.5;
+const NUMBER_LIFETIME: f32 = 0.9;
+const HIT_FLASH_SECONDS: f32 = 0.12;
+/// Smaller hits, like damage over time ticks, don't stop time or shake the camera
+const HIT_STOP_MIN_DAMAGE: f32 = 5.0;
+/// Real seconds the world freezes for on a solid hit
+const HIT_STOP_SECONDS: f32 = 0.06;
+const HIT_STOP_SPEED: f32 = 0.05;
+/// Vignette strength lost per second
+const VIGNETTE_DECAY: f32 = 1.5;
+/// Below this fraction of max health the vignette never fully goes away
+const LOW_HEALTH: f32 = 0.3;
+
+pub struct HitFeedbackPlugin;
+
+impl Plugin for HitFeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitStop>()
+            .add_systems(OnEnter(GameState::InGame), spawn_damage_vignette)
+            .add_systems(
+                Update,
+                (
+                    spawn_damage_numbers,
+                    float_damage_numbers,
+                    start_hit_flashes,
+                    update_hit_flashes,
+                    shake_on_player_damage,
+                    update_damage_vignette,
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            // Has to keep running so time always gets put back to normal
+            .add_systems(Update, hit_stop);
+    }
+}
+
+/// Number drifting up from where something got hurt.
+#[derive(Component)]
+struct DamageNumber {
+    position: Vec3,
+    drift: Vec3,
+    timer: Timer,
+}
+
+/// Meshes under this entity show up white until the timer runs out.
+#[derive(Component)]
+struct HitFlash {
+    timer: Timer,
+    /// Materials swapped out, put back when the flash ends
+    originals: Vec<(Entity, Handle<StandardMaterial>)>,
+}
+
+impl HitFlash {
+    fn new() -> Self {
+        Self {
+            timer: Timer::from_seconds(HIT_FLASH_SECONDS, TimerMode::Once),
+            originals: Vec::new(),
+        }
+    }
+}
+
+/// Real time left on the current hit-stop.
+#[derive(Resource, Default)]
+struct HitStop {
+    remaining: f32,
+}
+
+/// Red edges that flare up when the player gets hurt.
+#[derive(Component, Default)]
+struct DamageVignette {
+    intensity: f32,
+}
+
+fn spawn_damage_numbers(
+    mut commands: Commands,
+    mut dealt: EventReader<DamageDealt>,
+    mut count: Local<u32>,
+    target_query: Query<(&GlobalTransform, Has<Player>)>,
+) {
+    for hit in dealt.read() {
+        let Ok((transform, is_player)) = target_query.get(hit.target) else {
+            continue;
+        };
+        // Spread them out sideways so a burst of hits doesn't stack into one number
+        *count += 1;
+        let spread = (*count as f32 * 0.618).fract() - 0.5;
+        let color = if is_player {
+            Color::from(css::RED)
+        } else if hit.crit {
+            Color::from(css::GOLD)
+        } else {
+            hit.element.color()
+        };
+        let (text, font_size) = if hit.crit {
+            (format!("{:.0}!", hit.amount.ceil()), 36.0)
+        } else {
+            (format!("{:.0}", hit.amount.ceil()), 24.0)
+        };
+        commands.spawn((
+            Text::new(text),
+            TextFont {
+                font_size,
+                ..default()
+            },
+            TextColor(color),
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            // Off screen until it's been placed
+            Visibility::Hidden,
+            Pickable::IGNORE,
+            DamageNumber {
+                position: transform.translation() + Vec3::Y * 2.0,
+                drift: Vec3::new(spread, 1.0, 0.0) * NUMBER_RISE_SPEED,
+                timer: Timer::from_seconds(NUMBER_LIFETIME, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+fn float_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<ThirdPersonCamera>>,
+    mut number_query: Query<(
+        Entity,
+        &mut DamageNumber,
+        &mut Node,
+        &mut TextColor,
+        &mut Visibility,
+        &ComputedNode,
+    )>,
+) {
+    let Ok((camera, camera_transform)) = camera_query.single() else {
+        return;
+    };
+    for (entity, mut number, mut node, mut color, mut visibility, computed) in &mut number_query {
+        number.timer.tick(time.delta());
+        if number.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let drift = number.drift;
+        number.position += drift * time.delta_secs();
+        color.0.set_alpha(number.timer.fraction_remaining());
+
+        let Ok(screen) = camera.world_to_viewport(camera_transform, number.position) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        // Centred on the point, not hanging off to the right of it
+        let size = computed.size() * computed.inverse_scale_factor();
+        node.left = Val::Px(screen.x - size.x / 2.0);
+        node.top = Val::Px(screen.y - size.y / 2.0);
+        *visibility = Visibility::Inherited;
+    }
+}
+
+fn start_hit_flashes(
+    mut commands: Commands,
+    mut dealt: EventReader<DamageDealt>,
+    mut flash_query: Query<&mut HitFlash>,
+) {
+    for hit in dealt.read() {
+        match flash_query.get_mut(hit.target) {
+            Ok(mut flash) => flash.timer.reset(),
+            Err(_) => {
+                commands.entity(hit.target).try_insert(HitFlash::new());
+            }
+        }
+    }
+}
+
+fn update_hit_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut flash_material: Local<Option<Handle<StandardMaterial>>>,
+    mut flash_query: Query<(Entity, &mut HitFlash)>,
+    children_query: Query<&Children>,
+    mut mesh_query: Query<&mut MeshMaterial3d<StandardMaterial>>,
+) {
+    let flash_material = flash_material.get_or_insert_with(|| {
+        materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            emissive: LinearRgba::rgb(4.0, 4.0, 4.0),
+            unlit: true,
+            ..default()
+        })
+    });
+    for (entity, mut flash) in &mut flash_query {
+        // Swapping handles rather than editing the material, enemies share theirs
+        if flash.originals.is_empty() {
+            for mesh in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+                if let Ok(mut material) = mesh_query.get_mut(mesh) {
+                    let original = std::mem::replace(&mut material.0, flash_material.clone());
+                    flash.originals.push((mesh, original));
+                }
+            }
+        }
+
+        flash.timer.tick(time.delta());
+        if !flash.timer.finished() {
+            continue;
+        }
+        for (mesh, original) in flash.originals.drain(..) {
+            if let Ok(mut material) = mesh_query.get_mut(mesh) {
+                material.0 = original;
+            }
+        }
+        commands.entity(entity).try_remove::<HitFlash>();
+    }
+}
+
+/// Freezes the world for a few frames on a solid hit so it lands with some weight.
+fn hit_stop(
+    real_time: Res<Time<Real>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut hit_stop: ResMut<HitStop>,
+    mut dealt: EventReader<DamageDealt>,
+) {
+    if dealt.read().any(|hit| hit.amount >= HIT_STOP_MIN_DAMAGE) {
+        hit_stop.remaining = HIT_STOP_SECONDS;
+        virtual_time.set_relative_speed(HIT_STOP_SPEED);
+        return;
+    }
+    if hit_stop.remaining <= 0.0 {
+        return;
+    }
+    hit_stop.remaining -= real_time.delta_secs();
+    if hit_stop.remaining <= 0.0 {
+        virtual_time.set_relative_speed(1.0);
+    }
+}
+
+fn shake_on_player_damage(
+    mut dealt: EventReader<DamageDealt>,
+    player_query: Query<(), With<Player>>,
+    mut camera_query: Query<&mut ThirdPersonCamera>,
+    mut vignette_query: Query<&mut DamageVignette>,
+) {
+    for hit in dealt.read() {
+        if !player_query.contains(hit.target) {
+            continue;
+        }
+        if hit.amount >= HIT_STOP_MIN_DAMAGE {
+            for mut cam in &mut camera_query {
+                cam.add_trauma((hit.amount / 50.0).clamp(0.2, 0.6));
+            }
+        }
+        for mut vignette in &mut vignette_query {
+            vignette.intensity = (vignette.intensity + hit.amount / 30.0).min(1.0);
+        }
+    }
+}
+
+fn spawn_damage_vignette(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        ImageNode::new(images.add(vignette_image())).with_color(Color::NONE),
+        Pickable::IGNORE,
+        DamageVignette::default(),
+    ));
+}
+
+/// White at the edges of the screen, fading to clear well before the middle. Tinted in use.
+fn vignette_image() -> Image {
+    const SIZE: u32 = 64;
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let uv = Vec2::new(x as f32, y as f32) / (SIZE - 1) as f32 * 2.0 - Vec2::ONE;
+            let edge = ((uv.abs().max_element() - 0.6) / 0.4).clamp(0.0, 1.0);
+            data.extend_from_slice(&[255, 255, 255, (edge * edge * 255.0) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+fn update_damage_vignette(
+    time: Res<Time>,
+    health_query: Query<(&Health, &MaxHealth), With<Player>>,
+    mut vignette_query: Query<(&mut DamageVignette, &mut ImageNode)>,
+) {
+    // Keeps a faint glow going while health is low
+    let floor = health_query.single().map_or(0.0, |(health, max_health)| {
+        (1.0 - health.0 / (max_health.0 * LOW_HEALTH)).clamp(0.0, 1.0) * 0.4
+    });
+    for (mut vignette, mut image) in &mut vignette_query {
+        vignette.intensity = (vignette.intensity - VIGNETTE_DECAY * time.delta_secs()).max(floor);
+        image.color = Color::from(css::DARK_RED).with_alpha(vignette.intensity);
+    }
+}
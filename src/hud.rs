@@ -0,0 +1,383 @@
+use bevy::{color::palettes::css, prelude::*, window::PrimaryWindow};
+
+use crate::GameState;
+use crate::gameplay::traversal::{DASH_STAMINA_COST, DashCharges};
+use crate::gameplay::vitals::{Mana, Stamina};
+use crate::gameplay::water::Breath;
+use crate::player::{CastCooldown, FIREBALL_MANA_COST, Health, MaxHealth, Player, SelectedElement};
+
+/// Window height the HUD's pixel sizes are written for
+const REFERENCE_HEIGHT: f32 = 1080.0;
+const BAR_WIDTH: f32 = 300.0;
+const ICON_SIZE: f32 = 64.0;
+const MINIMAP_SIZE: f32 = 220.0;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<HudSettings>()
+            .init_resource::<HudSettings>()
+            .init_resource::<HudObjective>()
+            .add_systems(OnEnter(GameState::InGame), spawn_hud)
+            .add_systems(
+                Update,
+                (
+                    scale_hud,
+                    update_vital_bars,
+                    update_ability_icons,
+                    update_objective,
+                )
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct HudSettings {
+    /// On top of following the window size, 1 is the default size
+    pub scale: f32,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self { scale: 1.0 }
+    }
+}
+
+/// What the player should be doing right now, shown under the minimap.
+#[derive(Resource, Default)]
+pub struct HudObjective(pub Option<String>);
+
+/// Frame in the top right corner for the minimap to draw into.
+#[derive(Component)]
+pub struct MinimapSlot;
+
+/// Size in pixels at 1080p, kept in step with the window and `HudSettings`.
+#[derive(Component)]
+struct HudSize(Vec2);
+
+/// Font size in pixels at 1080p, like `HudSize`.
+#[derive(Component)]
+struct HudFontSize(f32);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Vital {
+    Health,
+    Mana,
+    Stamina,
+    Breath,
+}
+
+impl Vital {
+    fn color(self) -> Color {
+        Color::from(match self {
+            Vital::Health => css::GREEN,
+            Vital::Mana => css::ROYAL_BLUE,
+            Vital::Stamina => css::GOLD,
+            Vital::Breath => css::DEEP_SKY_BLUE,
+        })
+    }
+}
+
+#[derive(Component)]
+struct VitalBar(Vital);
+
+#[derive(Component)]
+struct VitalFill(Vital);
+
+#[derive(Component)]
+struct VitalText(Vital);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum AbilitySlot {
+    Fireball,
+    Dash,
+}
+
+#[derive(Component)]
+struct AbilityIcon(AbilitySlot);
+
+/// Dark overlay over an icon, as tall as the share of cooldown left.
+#[derive(Component)]
+struct AbilityCooldown(AbilitySlot);
+
+#[derive(Component)]
+struct AbilityText(AbilitySlot);
+
+#[derive(Component)]
+struct ObjectiveText;
+
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        // Under the menus, so it never eats their clicks
+        GlobalZIndex(-1),
+        children![
+            // Vitals, top left
+            (
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Vh(3.0),
+                    top: Val::Vh(3.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Vh(0.8),
+                    ..default()
+                },
+                children![
+                    vital_bar(Vital::Health, 28.0),
+                    vital_bar(Vital::Mana, 18.0),
+                    vital_bar(Vital::Stamina, 12.0),
+                    vital_bar(Vital::Breath, 12.0),
+                ],
+            ),
+            // Minimap and objective, top right
+            (
+                Node {
+                    position_type: PositionType::Absolute,
+                    right: Val::Vh(3.0),
+                    top: Val::Vh(3.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::FlexEnd,
+                    row_gap: Val::Vh(1.0),
+                    ..default()
+                },
+                children![
+                    (
+                        Node {
+                            border: UiRect::all(Val::Px(2.0)),
+                            overflow: Overflow::clip(),
+                            ..default()
+                        },
+                        HudSize(Vec2::splat(MINIMAP_SIZE)),
+                        BackgroundColor(Color::BLACK.with_alpha(0.4)),
+                        BorderColor(Color::from(css::GRAY)),
+                        MinimapSlot,
+                    ),
+                    (
+                        Text::default(),
+                        TextFont::default(),
+                        HudFontSize(20.0),
+                        TextLayout::new_with_justify(JustifyText::Right),
+                        ObjectiveText,
+                    ),
+                ],
+            ),
+            // Abilities, bottom middle
+            (
+                Node {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Vh(3.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Vh(1.0),
+                    ..default()
+                },
+                children![
+                    ability_icon(AbilitySlot::Fireball),
+                    ability_icon(AbilitySlot::Dash),
+                ],
+            ),
+        ],
+    ));
+}
+
+fn vital_bar(vital: Vital, height: f32) -> impl Bundle {
+    (
+        Node::default(),
+        HudSize(Vec2::new(BAR_WIDTH, height)),
+        BackgroundColor(Color::from(css::DARK_GRAY)),
+        VitalBar(vital),
+        children![
+            (
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(vital.color()),
+                VitalFill(vital),
+            ),
+            (
+                Text::default(),
+                TextFont::default(),
+                HudFontSize(height * 0.7),
+                TextColor(Color::WHITE),
+                Node {
+                    position_type: PositionType::Absolute,
+                    right: Val::Percent(2.0),
+                    ..default()
+                },
+                VitalText(vital),
+            ),
+        ],
+    )
+}
+
+fn ability_icon(slot: AbilitySlot) -> impl Bundle {
+    (
+        Node {
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::FlexEnd,
+            ..default()
+        },
+        HudSize(Vec2::splat(ICON_SIZE)),
+        BackgroundColor(Color::from(css::DARK_GRAY)),
+        BorderRadius::all(Val::Percent(10.0)),
+        AbilityIcon(slot),
+        children![
+            (
+                Node {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(0.0),
+                    ..default()
+                },
+                BackgroundColor(Color::BLACK.with_alpha(0.6)),
+                AbilityCooldown(slot),
+            ),
+            (
+                Text::default(),
+                TextFont::default(),
+                HudFontSize(16.0),
+                TextColor(Color::WHITE),
+                AbilityText(slot),
+            ),
+        ],
+    )
+}
+
+/// Follows the window height so the HUD takes up the same share of the screen at any
+/// resolution, times the player's own scale.
+fn scale_hud(
+    settings: Res<HudSettings>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut last_factor: Local<f32>,
+    added_query: Query<(), Or<(Added<HudSize>, Added<HudFontSize>)>>,
+    mut size_query: Query<(&HudSize, &mut Node)>,
+    mut font_query: Query<(&HudFontSize, &mut TextFont)>,
+) {
+    let Ok(window) = window_query.single() else {
+        return;
+    };
+    let factor = settings.scale * window.height() / REFERENCE_HEIGHT;
+    if factor == *last_factor && added_query.is_empty() {
+        return;
+    }
+    *last_factor = factor;
+
+    for (size, mut node) in &mut size_query {
+        node.width = Val::Px(size.0.x * factor);
+        node.height = Val::Px(size.0.y * factor);
+    }
+    for (size, mut font) in &mut font_query {
+        font.font_size = size.0 * factor;
+    }
+}
+
+fn update_vital_bars(
+    player_query: Query<(&Health, &MaxHealth, &Mana, &Stamina, &Breath), With<Player>>,
+    mut bar_query: Query<(&VitalBar, &mut Node), Without<VitalFill>>,
+    mut fill_query: Query<(&VitalFill, &mut Node), Without<VitalBar>>,
+    mut text_query: Query<(&VitalText, &mut Text)>,
+) {
+    let Ok((health, max_health, mana, stamina, breath)) = player_query.single() else {
+        return;
+    };
+    let values = |vital| match vital {
+        Vital::Health => (health.0, max_health.0),
+        Vital::Mana => (mana.0.current, mana.0.max),
+        Vital::Stamina => (stamina.0.current, stamina.0.max),
+        Vital::Breath => (breath.current, breath.max),
+    };
+
+    // Breath only shows while holding it
+    for (VitalBar(vital), mut node) in &mut bar_query {
+        let (current, max) = values(*vital);
+        node.display = if *vital == Vital::Breath && current >= max {
+            Display::None
+        } else {
+            Display::Flex
+        };
+    }
+    for (VitalFill(vital), mut node) in &mut fill_query {
+        let (current, max) = values(*vital);
+        node.width = Val::Percent((current / max).clamp(0.0, 1.0) * 100.0);
+    }
+    for (VitalText(vital), mut text) in &mut text_query {
+        if *vital != Vital::Breath {
+            let (current, max) = values(*vital);
+            text.0 = format!("{current:.0} / {max:.0}");
+        }
+    }
+}
+
+fn update_ability_icons(
+    player_query: Query<
+        (
+            &SelectedElement,
+            &CastCooldown,
+            &Mana,
+            &DashCharges,
+            &Stamina,
+        ),
+        With<Player>,
+    >,
+    mut icon_query: Query<(&AbilityIcon, &mut BackgroundColor), Without<AbilityCooldown>>,
+    mut cooldown_query: Query<(&AbilityCooldown, &mut Node)>,
+    mut text_query: Query<(&AbilityText, &mut Text)>,
+) {
+    let Ok((element, cast_cooldown, mana, charges, stamina)) = player_query.single() else {
+        return;
+    };
+    let ready = |slot| match slot {
+        AbilitySlot::Fireball => mana.0.current >= FIREBALL_MANA_COST,
+        AbilitySlot::Dash => charges.0 > 0 && stamina.0.current >= DASH_STAMINA_COST,
+    };
+
+    for (AbilityIcon(slot), mut color) in &mut icon_query {
+        let base = match slot {
+            AbilitySlot::Fireball => element.0.color(),
+            AbilitySlot::Dash => Color::from(css::SKY_BLUE),
+        };
+        // Greyed out while it can't be afforded
+        color.0 = if ready(*slot) {
+            base
+        } else {
+            base.with_alpha(0.3)
+        };
+    }
+    for (AbilityCooldown(slot), mut node) in &mut cooldown_query {
+        let remaining = match slot {
+            AbilitySlot::Fireball => cast_cooldown.0.fraction_remaining(),
+            // Stamina still to come back before the next dash
+            AbilitySlot::Dash => (1.0 - stamina.0.current / DASH_STAMINA_COST).clamp(0.0, 1.0),
+        };
+        node.height = Val::Percent(remaining * 100.0);
+    }
+    for (AbilityText(slot), mut text) in &mut text_query {
+        text.0 = match slot {
+            AbilitySlot::Fireball => format!("{:?}", element.0),
+            AbilitySlot::Dash => format!("Dash x{}", charges.0),
+        };
+    }
+}
+
+fn update_objective(
+    objective: Res<HudObjective>,
+    mut text_query: Query<&mut Text, With<ObjectiveText>>,
+) {
+    if !objective.is_changed() {
+        return;
+    }
+    for mut text in &mut text_query {
+        text.0 = objective.0.clone().unwrap_or_default();
+    }
+}
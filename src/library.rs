@@ -0,0 +1,134 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedFolder, io::Reader},
+    prelude::*,
+};
+use serde::de::DeserializeOwned;
+
+/// An asset read straight out of a RON file.
+pub trait RonAsset: Asset + DeserializeOwned {
+    /// File extension it's loaded from, e.g. `item.ron`
+    const EXTENSION: &'static str;
+}
+
+/// Registers a `RonAsset` and its loader.
+pub struct RonAssetPlugin<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for RonAssetPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: RonAsset> Plugin for RonAssetPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<T>().register_asset_loader(RonLoader::<T> {
+            extensions: [T::EXTENSION],
+            marker: PhantomData,
+        });
+    }
+}
+
+struct RonLoader<T> {
+    extensions: [&'static str; 1],
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: RonAsset> AssetLoader for RonLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+}
+
+/// Loads a whole folder of a `RonAsset` into a `FolderLibrary`.
+pub struct LibraryPlugin<T> {
+    /// Under `assets/`, e.g. `items`
+    folder: &'static str,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> LibraryPlugin<T> {
+    pub fn new(folder: &'static str) -> Self {
+        Self {
+            folder,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RonAsset> Plugin for LibraryPlugin<T> {
+    fn build(&self, app: &mut App) {
+        let folder = self.folder;
+        app.add_plugins(RonAssetPlugin::<T>::default())
+            .add_systems(
+                Startup,
+                move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                    commands.insert_resource(FolderLibrary::<T> {
+                        folder: asset_server.load_folder(folder),
+                        entries: HashMap::new(),
+                    });
+                },
+            )
+            .add_systems(Update, index_library::<T>);
+    }
+}
+
+/// Everything in one asset folder, keyed by file name without the extension.
+/// Filled in again whenever the folder changes on disk.
+#[derive(Resource)]
+pub struct FolderLibrary<T: Asset> {
+    folder: Handle<LoadedFolder>,
+    entries: HashMap<String, Handle<T>>,
+}
+
+impl<T: Asset> FolderLibrary<T> {
+    pub fn get<'a>(&self, assets: &'a Assets<T>, id: &str) -> Option<&'a T> {
+        self.entries.get(id).and_then(|handle| assets.get(handle))
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+}
+
+fn index_library<T: RonAsset>(
+    mut events: EventReader<AssetEvent<LoadedFolder>>,
+    folders: Res<Assets<LoadedFolder>>,
+    mut library: ResMut<FolderLibrary<T>>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&library.folder) {
+            continue;
+        }
+        let Some(folder) = folders.get(&library.folder) else {
+            continue;
+        };
+        let suffix = format!(".{}", T::EXTENSION);
+        library.entries = folder
+            .handles
+            .iter()
+            .filter_map(|handle| {
+                let id = handle.path()?.path().file_name()?.to_str()?;
+                let id = id.strip_suffix(&suffix)?.to_string();
+                Some((id, handle.clone().try_typed::<T>().ok()?))
+            })
+            .collect();
+        info!("Loaded {} {} files", library.entries.len(), T::EXTENSION);
+    }
+}
@@ -1,25 +1,82 @@
 use avian3d::prelude::*;
 use bevy::prelude::*;
-use bevy_panorbit_camera::PanOrbitCameraPlugin;
 use bevy_skein::SkeinPlugin;
 use bevy_tnua::{builtins::TnuaBuiltinDash, prelude::*};
 use bevy_tnua_avian3d::*;
 
 mod gameplay {
     pub mod attacks;
+    pub mod dialogue;
+    pub mod elements;
     pub mod enemies;
+    pub mod interaction;
+    pub mod items;
+    pub mod kill_volumes;
+    pub mod loot;
+    pub mod mechanisms;
     pub mod moving_platforms; // <-- Add this line!
+    pub mod progression;
+    pub mod quests;
+    pub mod stats;
+    pub mod status_effects;
+    pub mod traversal;
+    pub mod vitals;
+    pub mod water;
 }
 
+use gameplay::attacks::aim::AimPlugin;
 use gameplay::attacks::fireball::FireballPlugin;
+use gameplay::dialogue::DialoguePlugin;
+use gameplay::elements::{DealDamage, Element, ElementPlugin};
 use gameplay::enemies::melee_creep::MeleeCreepPlugin;
+use gameplay::interaction::InteractionPlugin;
+use gameplay::items::ItemPlugin;
+use gameplay::kill_volumes::KillVolumePlugin;
+use gameplay::loot::LootPlugin;
+use gameplay::mechanisms::MechanismPlugin;
 use gameplay::moving_platforms::MovingPlatformPlugin;
+use gameplay::progression::ProgressionPlugin;
+use gameplay::quests::QuestPlugin;
+use gameplay::stats::StatsPlugin;
+use gameplay::status_effects::StatusEffectPlugin;
+use gameplay::traversal::TraversalPlugin;
+use gameplay::vitals::VitalsPlugin;
+use gameplay::water::WaterPlugin;
+
+mod animation;
+use animation::CharacterAnimationPlugin;
+
+mod audio;
+use audio::GameAudioPlugin;
+
+mod camera;
+use camera::ThirdPersonCameraPlugin;
+
+mod hit_feedback;
+use hit_feedback::HitFeedbackPlugin;
+
+mod hud;
+use hud::HudPlugin;
+
+mod library;
+
+mod map;
+use map::MapPlugin;
+
+mod nameplates;
+use nameplates::NameplatePlugin;
+
+mod particles;
+use particles::ParticlePlugin;
+
+mod save;
+use save::SavePlugin;
 
 mod set_up;
 use set_up::SetupPlugin;
 
 mod player;
-use player::{Health, Player, PlayerPlugin};
+use player::{Player, PlayerPlugin};
 
 mod dev_utils;
 use dev_utils::DevUtilsPlugin;
@@ -28,27 +85,65 @@ mod ui;
 use ui::UiPlugin;
 
 fn main() {
+    let default_plugins = DefaultPlugins.set(AssetPlugin {
+        watch_for_changes_override: Some(true),
+        ..default()
+    });
+    // NO_AUDIO=1 for machines without a sound device, e.g. headless CI
+    let default_plugins = if std::env::var_os("NO_AUDIO").is_some() {
+        default_plugins.disable::<bevy::audio::AudioPlugin>()
+    } else {
+        default_plugins
+    };
+
     App::new()
-        .add_plugins(DefaultPlugins.set(AssetPlugin {
-            watch_for_changes_override: Some(true),
-            ..default()
-        }))
+        .add_plugins(default_plugins)
         .init_state::<GameState>()
         .add_plugins((
             PhysicsPlugins::default(),
             SkeinPlugin::default(),
             TnuaControllerPlugin::new(FixedUpdate),
             TnuaAvian3dPlugin::new(FixedUpdate),
-            PanOrbitCameraPlugin,
+        ))
+        .add_plugins((
+            ThirdPersonCameraPlugin,
             SetupPlugin,
             PlayerPlugin,
+            CharacterAnimationPlugin,
+            TraversalPlugin,
+            WaterPlugin,
             UiPlugin,
-            MovingPlatformPlugin,
+            MovingPlatformPlugin {
+                // STRICT_PLATFORMS=1 to hold a level with broken platform paths on the loading screen
+                strict: std::env::var_os("STRICT_PLATFORMS").is_some(),
+            },
             MeleeCreepPlugin,
             FireballPlugin,
-            // remove dev utils for final build
-            DevUtilsPlugin,
+            AimPlugin,
+            KillVolumePlugin,
+            MechanismPlugin,
         ))
+        .add_plugins((
+            SavePlugin,
+            ItemPlugin,
+            StatsPlugin,
+            StatusEffectPlugin,
+            ProgressionPlugin,
+            ElementPlugin,
+            HitFeedbackPlugin,
+            NameplatePlugin,
+            VitalsPlugin,
+            HudPlugin,
+            MapPlugin,
+            QuestPlugin,
+            DialoguePlugin,
+            InteractionPlugin,
+            LootPlugin,
+        ))
+        .add_plugins((GameAudioPlugin, ParticlePlugin))
+        // remove dev utils for final build
+        .add_plugins(DevUtilsPlugin)
+        .add_event::<SpikeHit>()
         .add_systems(Update, spike_damage_system)
         .run();
 }
@@ -63,6 +158,10 @@ struct Spikes {
 #[reflect(Component)]
 pub struct SpikeDamageCooldown(Timer);
 
+/// The player got spiked, at the spot they were standing.
+#[derive(Event)]
+pub struct SpikeHit(pub Vec3);
+
 #[derive(States, Debug, Clone, Eq, PartialEq, Hash, Default)]
 pub enum GameState {
     #[default]
@@ -72,9 +171,11 @@ pub enum GameState {
 
 fn spike_damage_system(
     time: Res<Time>,
+    mut damage: EventWriter<DealDamage>,
+    mut spike_hits: EventWriter<SpikeHit>,
     mut health_query: Query<
         (
-            &mut Health,
+            Entity,
             &Transform,
             &mut SpikeDamageCooldown,
             &mut TnuaController,
@@ -83,7 +184,7 @@ fn spike_damage_system(
     >,
     spike_query: Query<(&Spikes, &Transform)>,
 ) {
-    if let Ok((mut health, player_transform, mut cooldown, mut tnua_controller)) =
+    if let Ok((player, player_transform, mut cooldown, mut tnua_controller)) =
         health_query.single_mut()
     {
         cooldown.0.tick(time.delta());
@@ -95,7 +196,12 @@ fn spike_damage_system(
 
             if distance < 3.0 && cooldown.0.finished() {
                 // Damage
-                health.0 = (health.0 - spike.damage).max(0.0);
+                damage.write(DealDamage {
+                    target: player,
+                    amount: spike.damage,
+                    element: Element::Physical,
+                });
+                spike_hits.write(SpikeHit(player_pos));
 
                 // Knockback direction using Tnua impulse
                 let knock_dir = (player_pos - spike_pos).normalize_or_zero();
@@ -0,0 +1,578 @@
+use std::collections::HashSet;
+
+use avian3d::prelude::*;
+use bevy::{
+    asset::RenderAssetUsages,
+    color::palettes::css,
+    image::ImageSampler,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::GameState;
+use crate::gameplay::dialogue::ActiveDialogue;
+use crate::gameplay::elements::IceSheet;
+use crate::gameplay::enemies::melee_creep::Enemy;
+use crate::gameplay::kill_volumes::Checkpoint;
+use crate::gameplay::water::WaterVolume;
+use crate::hud::MinimapSlot;
+use crate::player::Player;
+use crate::save::{SaveData, SaveGame};
+use crate::set_up::CurrentLevel;
+
+/// Everything around the player this close gets uncovered
+const REVEAL_RADIUS: f32 = 15.0;
+/// Metres from the player to the edge of the minimap
+const MINIMAP_RANGE: f32 = 30.0;
+/// Enemies only show up on the map this close to the player
+const ENEMY_RANGE: f32 = 25.0;
+/// Biggest map texture we'll build, in metres (and pixels) a side
+const MAX_MAP_SIZE: i32 = 1024;
+/// Colliders tend to arrive in a burst while the level spawns, wait for it to settle
+const REBUILD_DELAY: f32 = 0.5;
+/// Seconds between writing newly explored ground to the save
+const SAVE_INTERVAL: f32 = 5.0;
+
+const UNEXPLORED: [u8; 4] = [10, 10, 14, 255];
+const EMPTY: [u8; 4] = [28, 28, 34, 255];
+const WATER: [u8; 4] = [40, 90, 170, 255];
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapOpen>()
+            .init_resource::<ExploredMap>()
+            .add_systems(Startup, create_level_map)
+            .add_systems(
+                OnEnter(GameState::InGame),
+                (restore_explored, spawn_full_map),
+            )
+            .add_systems(
+                Update,
+                (
+                    rebuild_level_map,
+                    reveal_map,
+                    paint_level_map,
+                    save_explored,
+                    toggle_map,
+                    attach_minimap,
+                    update_map_views,
+                    update_map_markers,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Set while the full-screen map is up.
+#[derive(Resource, Default)]
+pub struct MapOpen(pub bool);
+
+/// Ground the player has seen in the current level, in one metre cells. Kept in the save file.
+#[derive(Resource, Default)]
+pub struct ExploredMap {
+    pub cells: HashSet<IVec2>,
+    /// Uncovered since the map was last painted
+    newly_revealed: Vec<IVec2>,
+}
+
+/// Top-down picture of the level, one pixel per metre, built from its static colliders.
+#[derive(Resource)]
+struct LevelMap {
+    image: Handle<Image>,
+    /// World XZ of the top left pixel
+    origin: IVec2,
+    size: UVec2,
+    /// Top of the highest solid thing in each cell, `None` where there's nothing
+    heights: Vec<Option<f32>>,
+    water: Vec<bool>,
+    /// Lowest and highest of `heights`, for shading
+    height_range: (f32, f32),
+}
+
+impl LevelMap {
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        let local = cell - self.origin;
+        (local.x >= 0
+            && local.y >= 0
+            && local.x < self.size.x as i32
+            && local.y < self.size.y as i32)
+            .then(|| (local.y as u32 * self.size.x + local.x as u32) as usize)
+    }
+
+    /// World XZ area the whole texture covers
+    fn world_rect(&self) -> Rect {
+        Rect::from_corners(
+            self.origin.as_vec2(),
+            self.origin.as_vec2() + self.size.as_vec2(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MapViewKind {
+    Minimap,
+    Full,
+}
+
+/// Image node showing some part of the level map.
+#[derive(Component)]
+struct MapView {
+    kind: MapViewKind,
+    /// World XZ area it's showing right now
+    world_rect: Rect,
+}
+
+/// Dot on a map view following something around the level.
+#[derive(Component)]
+struct MapMarker {
+    target: Entity,
+}
+
+#[derive(Component)]
+struct FullMap;
+
+fn world_cell(position: Vec3) -> IVec2 {
+    IVec2::new(position.x.floor() as i32, position.z.floor() as i32)
+}
+
+fn create_level_map(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.insert_resource(LevelMap {
+        image: images.add(map_image(UVec2::ONE, UNEXPLORED.to_vec())),
+        origin: IVec2::ZERO,
+        size: UVec2::ONE,
+        heights: vec![None],
+        water: vec![false],
+        height_range: (0.0, 0.0),
+    });
+}
+
+fn map_image(size: UVec2, data: Vec<u8>) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    // Crisp cells rather than a blurry smear when zoomed in
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+fn restore_explored(
+    save: Res<SaveData>,
+    level: Res<CurrentLevel>,
+    mut explored: ResMut<ExploredMap>,
+    mut map: ResMut<LevelMap>,
+) {
+    explored.cells = save
+        .explored_by_level
+        .get(&level.0)
+        .into_iter()
+        .flatten()
+        .map(|&(x, z)| IVec2::new(x, z))
+        .collect();
+    explored.newly_revealed.clear();
+    // Everything changed at once, so repaint the lot
+    map.set_changed();
+}
+
+/// Rasterises every static collider's bounds into the map once the level has
+/// finished spawning, and again if more level turns up later.
+fn rebuild_level_map(
+    time: Res<Time>,
+    mut pending: Local<Option<f32>>,
+    mut built_once: Local<bool>,
+    added_query: Query<
+        (Entity, Option<&ColliderOf>),
+        (Added<ColliderAabb>, Without<Sensor>, Without<IceSheet>),
+    >,
+    collider_query: Query<
+        (Entity, &ColliderAabb, Option<&ColliderOf>),
+        (With<Collider>, Without<Sensor>, Without<IceSheet>),
+    >,
+    body_query: Query<&RigidBody>,
+    water_query: Query<&ColliderAabb, With<WaterVolume>>,
+    mut map: ResMut<LevelMap>,
+) {
+    // Colliders without a body of their own are part of the static world
+    let is_static = |entity: Entity, collider_of: Option<&ColliderOf>| {
+        let body = collider_of.map_or(entity, |of| of.body);
+        body_query.get(body).map_or(true, |body| body.is_static())
+    };
+    if !*built_once
+        || added_query
+            .iter()
+            .any(|(entity, collider_of)| is_static(entity, collider_of))
+    {
+        *built_once = true;
+        *pending = Some(REBUILD_DELAY);
+    }
+    let Some(remaining) = pending.as_mut() else {
+        return;
+    };
+    *remaining -= time.delta_secs();
+    if *remaining > 0.0 {
+        return;
+    }
+    *pending = None;
+
+    let solids: Vec<&ColliderAabb> = collider_query
+        .iter()
+        .filter(|(entity, _, collider_of)| is_static(*entity, *collider_of))
+        .map(|(_, aabb, _)| aabb)
+        .collect();
+    let Some(bounds) = solids
+        .iter()
+        .map(|aabb| Rect::new(aabb.min.x, aabb.min.z, aabb.max.x, aabb.max.z))
+        .reduce(|a, b| a.union(b))
+    else {
+        return;
+    };
+
+    // Padded so the minimap never samples past the edge of the texture
+    let margin = MINIMAP_RANGE.ceil() as i32;
+    let origin = bounds.min.floor().as_ivec2() - IVec2::splat(margin);
+    let size = (bounds.max.ceil().as_ivec2() + IVec2::splat(margin) - origin)
+        .clamp(IVec2::ONE, IVec2::splat(MAX_MAP_SIZE))
+        .as_uvec2();
+    let cells = (size.x * size.y) as usize;
+    map.origin = origin;
+    map.size = size;
+    map.heights = vec![None; cells];
+    map.water = vec![false; cells];
+
+    let cells_under = |min: Vec3, max: Vec3| {
+        let min = IVec2::new(min.x.floor() as i32, min.z.floor() as i32);
+        let max = IVec2::new(max.x.ceil() as i32, max.z.ceil() as i32);
+        (min.y..max.y).flat_map(move |z| (min.x..max.x).map(move |x| IVec2::new(x, z)))
+    };
+    let mut range = (f32::MAX, f32::MIN);
+    for aabb in solids {
+        for cell in cells_under(aabb.min, aabb.max) {
+            let Some(index) = map.index(cell) else {
+                continue;
+            };
+            let top = map.heights[index].map_or(aabb.max.y, |height| height.max(aabb.max.y));
+            map.heights[index] = Some(top);
+            range = (range.0.min(top), range.1.max(top));
+        }
+    }
+    for aabb in &water_query {
+        for cell in cells_under(aabb.min, aabb.max) {
+            if let Some(index) = map.index(cell) {
+                map.water[index] = true;
+            }
+        }
+    }
+    map.height_range = range;
+}
+
+fn reveal_map(
+    mut last_cell: Local<Option<IVec2>>,
+    mut explored: ResMut<ExploredMap>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+) {
+    let Ok(transform) = player_query.single() else {
+        return;
+    };
+    let centre = world_cell(transform.translation());
+    if *last_cell == Some(centre) {
+        return;
+    }
+    *last_cell = Some(centre);
+
+    // Only flag a change when something new turned up, repainting isn't free
+    let radius = REVEAL_RADIUS.ceil() as i32;
+    let mut found = false;
+    let ExploredMap {
+        cells,
+        newly_revealed,
+    } = explored.bypass_change_detection();
+    for z in -radius..=radius {
+        for x in -radius..=radius {
+            let cell = centre + IVec2::new(x, z);
+            if (cell - centre).as_vec2().length() <= REVEAL_RADIUS && cells.insert(cell) {
+                newly_revealed.push(cell);
+                found = true;
+            }
+        }
+    }
+    if found {
+        explored.set_changed();
+    }
+}
+
+/// Colour of one cell on the map
+fn cell_pixel(map: &LevelMap, index: usize, explored: bool) -> [u8; 4] {
+    let (low, high) = map.height_range;
+    if !explored {
+        UNEXPLORED
+    } else if map.water[index] {
+        WATER
+    } else if let Some(height) = map.heights[index] {
+        // Higher ground is lighter
+        let shade = if high > low {
+            (height - low) / (high - low)
+        } else {
+            0.5
+        };
+        let value = (90.0 + shade * 130.0) as u8;
+        [value, value, value, 255]
+    } else {
+        EMPTY
+    }
+}
+
+/// Repaints the whole map when the level changes, otherwise just touches up the
+/// cells that were uncovered since last time.
+fn paint_level_map(
+    map: Res<LevelMap>,
+    mut explored: ResMut<ExploredMap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if map.is_changed() {
+        explored.bypass_change_detection().newly_revealed.clear();
+        let mut data = Vec::with_capacity((map.size.x * map.size.y * 4) as usize);
+        for y in 0..map.size.y as i32 {
+            for x in 0..map.size.x as i32 {
+                let cell = map.origin + IVec2::new(x, y);
+                let index = map.index(cell).unwrap_or_default();
+                data.extend_from_slice(&cell_pixel(&map, index, explored.cells.contains(&cell)));
+            }
+        }
+        if let Some(image) = images.get_mut(&map.image) {
+            *image = map_image(map.size, data);
+        }
+        return;
+    }
+    if explored.newly_revealed.is_empty() {
+        return;
+    }
+    let revealed = std::mem::take(&mut explored.bypass_change_detection().newly_revealed);
+    let Some(data) = images
+        .get_mut(&map.image)
+        .and_then(|image| image.data.as_mut())
+    else {
+        return;
+    };
+    for cell in revealed {
+        if let Some(index) = map.index(cell) {
+            data[index * 4..index * 4 + 4].copy_from_slice(&cell_pixel(&map, index, true));
+        }
+    }
+}
+
+fn save_explored(
+    time: Res<Time>,
+    mut since_save: Local<f32>,
+    mut unsaved: Local<bool>,
+    explored: Res<ExploredMap>,
+    level: Res<CurrentLevel>,
+    mut save: ResMut<SaveData>,
+    mut save_game: EventWriter<SaveGame>,
+) {
+    *since_save += time.delta_secs();
+    *unsaved |= explored.is_changed();
+    if !*unsaved || *since_save < SAVE_INTERVAL {
+        return;
+    }
+    *since_save = 0.0;
+    *unsaved = false;
+    save.explored_by_level.insert(
+        level.0.clone(),
+        explored.cells.iter().map(|cell| (cell.x, cell.y)).collect(),
+    );
+    save_game.write(SaveGame);
+}
+
+fn toggle_map(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    dialogue: Res<ActiveDialogue>,
+    mut open: ResMut<MapOpen>,
+    mut panel_query: Query<&mut Visibility, With<FullMap>>,
+) {
+    // The d-pad picks dialogue replies while someone's talking
+    let pressed = keyboard.just_pressed(KeyCode::KeyM)
+        || (dialogue.0.is_none()
+            && gamepads
+                .iter()
+                .any(|pad| pad.just_pressed(GamepadButton::DPadUp)));
+    if !pressed {
+        return;
+    }
+    open.0 = !open.0;
+    for mut visibility in &mut panel_query {
+        *visibility = if open.0 {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn spawn_full_map(mut commands: Commands, map: Res<LevelMap>) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.7)),
+        Visibility::Hidden,
+        FullMap,
+        children![(
+            Node {
+                height: Val::Vh(85.0),
+                ..default()
+            },
+            ImageNode::new(map.image.clone()),
+            MapView {
+                kind: MapViewKind::Full,
+                world_rect: map.world_rect(),
+            },
+        )],
+    ));
+}
+
+/// The HUD leaves an empty frame for the minimap, fill it in.
+fn attach_minimap(
+    mut commands: Commands,
+    map: Res<LevelMap>,
+    slot_query: Query<Entity, Added<MinimapSlot>>,
+) {
+    for slot in &slot_query {
+        commands.entity(slot).with_child((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            ImageNode::new(map.image.clone()),
+            MapView {
+                kind: MapViewKind::Minimap,
+                world_rect: Rect::default(),
+            },
+        ));
+    }
+}
+
+fn update_map_views(
+    map: Res<LevelMap>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    mut view_query: Query<(&mut MapView, &mut ImageNode, &mut Node)>,
+) {
+    let Ok(player_transform) = player_query.single() else {
+        return;
+    };
+    let player = player_transform.translation().xz();
+    for (mut view, mut image, mut node) in &mut view_query {
+        match view.kind {
+            // North up, centred on the player
+            MapViewKind::Minimap => {
+                view.world_rect = Rect::from_center_half_size(player, Vec2::splat(MINIMAP_RANGE));
+                image.rect = Some(Rect::from_center_half_size(
+                    player - map.origin.as_vec2(),
+                    Vec2::splat(MINIMAP_RANGE),
+                ));
+            }
+            MapViewKind::Full => {
+                if map.is_changed() {
+                    view.world_rect = map.world_rect();
+                    node.aspect_ratio = Some(map.size.x as f32 / map.size.y as f32);
+                }
+            }
+        }
+    }
+}
+
+fn update_map_markers(
+    mut commands: Commands,
+    mut tracked: Local<HashSet<(Entity, Entity)>>,
+    explored: Res<ExploredMap>,
+    view_query: Query<(Entity, &MapView)>,
+    target_query: Query<
+        (Entity, &GlobalTransform, Has<Player>, Has<Enemy>),
+        Or<(With<Player>, With<Enemy>, With<Checkpoint>)>,
+    >,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    mut marker_query: Query<(Entity, &MapMarker, &ChildOf, &mut Node, &mut Visibility)>,
+) {
+    let Ok(player_transform) = player_query.single() else {
+        return;
+    };
+    let player = player_transform.translation();
+
+    for (view, _) in &view_query {
+        for (target, _, is_player, is_enemy) in &target_query {
+            if !tracked.insert((view, target)) {
+                continue;
+            }
+            let (color, size) = if is_player {
+                (css::WHITE, 10.0)
+            } else if is_enemy {
+                (css::RED, 7.0)
+            } else {
+                (css::GOLD, 8.0)
+            };
+            commands.entity(view).with_child((
+                Node {
+                    position_type: PositionType::Absolute,
+                    width: Val::Px(size),
+                    height: Val::Px(size),
+                    // Centred on the spot rather than hanging off it
+                    margin: UiRect {
+                        left: Val::Px(-size / 2.0),
+                        top: Val::Px(-size / 2.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                BackgroundColor(Color::from(color)),
+                BorderRadius::MAX,
+                Visibility::Hidden,
+                MapMarker { target },
+            ));
+        }
+    }
+
+    for (marker, MapMarker { target }, child_of, mut node, mut visibility) in &mut marker_query {
+        let view = child_of.parent();
+        let (Ok((_, transform, is_player, is_enemy)), Ok((_, map_view))) =
+            (target_query.get(*target), view_query.get(view))
+        else {
+            // Whatever it was following is gone
+            commands.entity(marker).despawn();
+            tracked.remove(&(view, *target));
+            continue;
+        };
+        let position = transform.translation();
+        let shown = if is_player {
+            true
+        } else if is_enemy {
+            position.distance(player) < ENEMY_RANGE
+        } else {
+            explored.cells.contains(&world_cell(position))
+        };
+        let rect = map_view.world_rect;
+        let uv = (position.xz() - rect.min) / rect.size();
+        if !shown || uv.cmplt(Vec2::ZERO).any() || uv.cmpgt(Vec2::ONE).any() {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        node.left = Val::Percent(uv.x * 100.0);
+        node.top = Val::Percent(uv.y * 100.0);
+        *visibility = Visibility::Inherited;
+    }
+}
@@ -0,0 +1,224 @@
+use avian3d::prelude::*;
+use bevy::{color::palettes::css, prelude::*};
+
+use crate::GameState;
+use crate::camera::ThirdPersonCamera;
+use crate::gameplay::elements::DamageDealt;
+use crate::gameplay::enemies::melee_creep::Enemy;
+use crate::player::Player;
+
+/// Seconds a bar stays up after the last hit, not counting the fade
+const SHOW_SECONDS: f32 = 4.0;
+const FADE_SECONDS: f32 = 1.0;
+/// Bars further away than this aren't worth drawing
+const MAX_DISTANCE: f32 = 35.0;
+/// Distance at which a bar is drawn at its full size
+const REFERENCE_DISTANCE: f32 = 6.0;
+const BAR_WIDTH: f32 = 80.0;
+const BAR_HEIGHT: f32 = 8.0;
+const NAME_FONT_SIZE: f32 = 16.0;
+/// How far above the enemy's origin the bar floats
+const BAR_HEIGHT_OFFSET: f32 = 2.2;
+
+pub struct NameplatePlugin;
+
+impl Plugin for NameplatePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Nameplate>().add_systems(
+            Update,
+            (spawn_enemy_bars, show_damaged_bars, update_enemy_bars)
+                .chain()
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Name shown over an enemy's health bar. Enemies without one just get the bar.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct Nameplate(pub String);
+
+/// Screen-space bar following an enemy around.
+#[derive(Component)]
+struct EnemyBar {
+    enemy: Entity,
+    /// Health the enemy started with, `Enemy` only tracks what's left
+    max_health: f32,
+    /// Seconds until it's faded out completely
+    remaining: f32,
+    background: Entity,
+    fill: Entity,
+    name: Entity,
+}
+
+fn spawn_enemy_bars(
+    mut commands: Commands,
+    enemy_query: Query<(Entity, &Enemy, Option<&Nameplate>), Added<Enemy>>,
+) {
+    for (enemy_entity, enemy, nameplate) in &enemy_query {
+        // Parent node (holds the name above the bar)
+        let root = commands
+            .spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(2.0),
+                    ..default()
+                },
+                Visibility::Hidden,
+                Pickable::IGNORE,
+            ))
+            .id();
+
+        // Name text
+        let name = commands
+            .spawn((
+                Text::new(nameplate.map(|plate| plate.0.clone()).unwrap_or_default()),
+                TextFont {
+                    font_size: NAME_FONT_SIZE,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                Pickable::IGNORE,
+            ))
+            .id();
+
+        // Bar background
+        let background = commands
+            .spawn((
+                Node {
+                    width: Val::Px(BAR_WIDTH),
+                    height: Val::Px(BAR_HEIGHT),
+                    ..default()
+                },
+                BackgroundColor(Color::from(css::DARK_GRAY)),
+                Pickable::IGNORE,
+            ))
+            .id();
+
+        // Fill node (foreground)
+        let fill = commands
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(Color::from(css::RED)),
+                Pickable::IGNORE,
+            ))
+            .id();
+
+        commands.entity(background).add_child(fill);
+        commands.entity(root).add_children(&[name, background]);
+        commands.entity(root).insert(EnemyBar {
+            enemy: enemy_entity,
+            max_health: enemy.health.max(1.0),
+            remaining: 0.0,
+            background,
+            fill,
+            name,
+        });
+    }
+}
+
+fn show_damaged_bars(mut dealt: EventReader<DamageDealt>, mut bar_query: Query<&mut EnemyBar>) {
+    for hit in dealt.read() {
+        for mut bar in &mut bar_query {
+            if bar.enemy == hit.target {
+                bar.remaining = SHOW_SECONDS + FADE_SECONDS;
+            }
+        }
+    }
+}
+
+fn update_enemy_bars(
+    mut commands: Commands,
+    time: Res<Time>,
+    spatial_query: SpatialQuery,
+    sensor_query: Query<(), With<Sensor>>,
+    camera_query: Query<(&Camera, &GlobalTransform, &ThirdPersonCamera)>,
+    player_query: Query<Entity, With<Player>>,
+    enemy_query: Query<(&Enemy, &GlobalTransform)>,
+    mut bar_query: Query<(
+        Entity,
+        &mut EnemyBar,
+        &mut Node,
+        &mut Visibility,
+        &ComputedNode,
+    )>,
+    mut node_query: Query<(&mut Node, &mut BackgroundColor), Without<EnemyBar>>,
+    mut text_query: Query<(&mut TextFont, &mut TextColor)>,
+) {
+    let Ok((camera, camera_transform, cam)) = camera_query.single() else {
+        return;
+    };
+    let filter = SpatialQueryFilter::from_excluded_entities(player_query.iter());
+
+    for (bar_entity, mut bar, mut node, mut visibility, computed) in &mut bar_query {
+        let Ok((enemy, enemy_transform)) = enemy_query.get(bar.enemy) else {
+            // Enemy's gone, take the bar with it
+            commands.entity(bar_entity).despawn();
+            continue;
+        };
+        bar.remaining = (bar.remaining - time.delta_secs()).max(0.0);
+        // Whatever the camera is locked on to always shows its health
+        if cam.lock_on == Some(bar.enemy) {
+            bar.remaining = bar.remaining.max(FADE_SECONDS);
+        }
+        *visibility = Visibility::Hidden;
+        if bar.remaining <= 0.0 || enemy.health <= 0.0 {
+            continue;
+        }
+
+        let head = enemy_transform.translation() + Vec3::Y * BAR_HEIGHT_OFFSET;
+        let eye = camera_transform.translation();
+        let distance = eye.distance(head);
+        if distance > MAX_DISTANCE {
+            continue;
+        }
+        let Ok(screen) = camera.world_to_viewport(camera_transform, head) else {
+            continue;
+        };
+        // Hidden behind a wall, enemies and triggers don't count
+        let occluded = Dir3::new(head - eye).is_ok_and(|direction| {
+            spatial_query
+                .cast_ray_predicate(eye, direction, distance, true, &filter, &|entity| {
+                    !sensor_query.contains(entity) && !enemy_query.contains(entity)
+                })
+                .is_some()
+        });
+        if occluded {
+            continue;
+        }
+
+        // Smaller further away, but never unreadably small or huge up close
+        let scale = (REFERENCE_DISTANCE / distance).clamp(0.4, 1.2);
+        let alpha = (bar.remaining / FADE_SECONDS).min(1.0);
+        let health_percent = (enemy.health / bar.max_health).clamp(0.0, 1.0);
+
+        if let Ok((mut background, mut color)) = node_query.get_mut(bar.background) {
+            background.width = Val::Px(BAR_WIDTH * scale);
+            background.height = Val::Px(BAR_HEIGHT * scale);
+            color.0 = Color::from(css::DARK_GRAY).with_alpha(alpha);
+        }
+        if let Ok((mut fill, mut color)) = node_query.get_mut(bar.fill) {
+            fill.width = Val::Percent(health_percent * 100.0);
+            color.0 = Color::from(css::RED).with_alpha(alpha);
+        }
+        if let Ok((mut font, mut color)) = text_query.get_mut(bar.name) {
+            // Changing the size re-lays out the text, so only when it actually changes
+            if font.font_size != NAME_FONT_SIZE * scale {
+                font.font_size = NAME_FONT_SIZE * scale;
+            }
+            color.0 = Color::WHITE.with_alpha(alpha);
+        }
+
+        // Centred over the enemy's head
+        let size = computed.size() * computed.inverse_scale_factor();
+        node.left = Val::Px(screen.x - size.x / 2.0);
+        node.top = Val::Px(screen.y - size.y);
+        *visibility = Visibility::Inherited;
+    }
+}
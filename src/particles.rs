@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+
+use avian3d::prelude::*;
+use bevy::{
+    ecs::system::SystemParam,
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::gameplay::attacks::fireball::{Fireball, FireballImpact};
+use crate::gameplay::enemies::melee_creep::EnemyKilled;
+use crate::gameplay::traversal::DashStarted;
+use crate::library::{FolderLibrary, LibraryPlugin, RonAsset};
+use crate::{GameState, SpikeHit};
+
+/// Hidden particles made up front, so the first big burst doesn't have to spawn any
+const PREWARMED_PARTICLES: usize = 256;
+/// Past this many, new particles get dropped instead of growing the pool
+const MAX_PARTICLES: usize = 2048;
+/// How long afterimages keep coming once a dash starts
+const DASH_AFTERIMAGE_SECONDS: f32 = 0.25;
+/// Colours a particle steps through from birth to death
+const COLOR_STEPS: usize = 8;
+
+/// Simple CPU particles. Every particle is a pooled entity, hidden and handed back
+/// when it dies. Particles with the same colours share their materials.
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(LibraryPlugin::<EffectDefinition>::new("effects"))
+            .register_type::<ParticleEmitter>()
+            .add_event::<PlayEffect>()
+            .add_systems(OnEnter(GameState::InGame), prewarm_particles)
+            .add_systems(
+                Update,
+                (
+                    (
+                        fireball_trails,
+                        fireball_impacts,
+                        dash_afterimages,
+                        spike_sparks,
+                        death_bursts,
+                    ),
+                    emit_particles,
+                    simulate_particles,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Read from `assets/effects/<id>.effect.ron`.
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct EffectDefinition {
+    /// Particles let out at once when the effect is played
+    #[serde(default)]
+    pub burst: u32,
+    /// Particles a second while a `ParticleEmitter` runs it
+    #[serde(default)]
+    pub rate: f32,
+    /// Seconds, picked at random between the two
+    pub lifetime: (f32, f32),
+    /// Metres per second, picked at random between the two
+    #[serde(default)]
+    pub speed: (f32, f32),
+    /// How far particles stray from the effect's direction. 0 is dead straight,
+    /// anything past 2 is every which way.
+    #[serde(default)]
+    pub spread: f32,
+    /// Particles start somewhere in a ball this big around the origin
+    #[serde(default)]
+    pub radius: f32,
+    /// Downward pull in metres per second squared, negative floats up
+    #[serde(default)]
+    pub gravity: f32,
+    /// Fraction of speed lost per second
+    #[serde(default)]
+    pub drag: f32,
+    /// How much of the emitter's own velocity particles keep
+    #[serde(default)]
+    pub inherit_velocity: f32,
+    /// At birth and at death
+    pub size: (f32, f32),
+    /// Linear RGBA at birth and at death. Glowing effects should fade to black.
+    pub color: ([f32; 4], [f32; 4]),
+    #[serde(default)]
+    pub shape: ParticleShape,
+    /// Unlit and added on top of whatever is behind, for fire and sparks
+    #[serde(default)]
+    pub glow: bool,
+}
+
+impl RonAsset for EffectDefinition {
+    const EXTENSION: &'static str = "effect.ron";
+}
+
+/// Effect definitions by id, what `PlayEffect` and `ParticleEmitter` name.
+pub type EffectLibrary = FolderLibrary<EffectDefinition>;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParticleShape {
+    #[default]
+    Sphere,
+    Cube,
+    /// Player sized at size 1, for afterimages
+    Capsule,
+}
+
+/// Play an effect's burst once, at a point.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PlayEffect {
+    /// Effect id, e.g. `fireball_impact`
+    pub effect: &'static str,
+    pub position: Vec3,
+    /// Where the spread is centred
+    pub direction: Vec3,
+    /// Multiplies the effect's colours, e.g. by a fireball's element
+    pub tint: Option<Color>,
+}
+
+impl PlayEffect {
+    pub fn at(effect: &'static str, position: Vec3) -> Self {
+        Self {
+            effect,
+            position,
+            direction: Vec3::Y,
+            tint: None,
+        }
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = Some(tint);
+        self
+    }
+}
+
+/// Keeps letting out an effect's `rate` from wherever this entity is, aimed
+/// along its up. Can go on things in Blender too, like torches.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component, Default)]
+pub struct ParticleEmitter {
+    /// Effect id, e.g. `fireball_trail`
+    pub effect: String,
+    #[reflect(default)]
+    pub tint: Option<Color>,
+    /// Removes itself once this runs out, runs for good without one
+    #[reflect(default)]
+    pub duration: Option<Timer>,
+    /// Part of a particle carried over from last frame
+    #[reflect(ignore)]
+    owed: f32,
+}
+
+impl ParticleEmitter {
+    pub fn new(effect: impl Into<String>) -> Self {
+        Self {
+            effect: effect.into(),
+            ..default()
+        }
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = Some(tint);
+        self
+    }
+
+    pub fn for_seconds(mut self, seconds: f32) -> Self {
+        self.duration = Some(Timer::from_seconds(seconds, TimerMode::Once));
+        self
+    }
+}
+
+/// One pooled particle, hidden and back in the pool once `age` passes `lifetime`.
+#[derive(Component, Default)]
+struct Particle {
+    alive: bool,
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    gravity: f32,
+    drag: f32,
+    size: (f32, f32),
+    /// Index into `ParticlePool::ramps`
+    ramp: usize,
+    /// Which of the ramp's materials it's showing
+    step: usize,
+}
+
+#[derive(Resource)]
+struct ParticlePool {
+    /// Hidden particles ready to be reused
+    free: Vec<Entity>,
+    total: usize,
+    /// One for each `ParticleShape`
+    meshes: [Handle<Mesh>; 3],
+    /// Materials going from birth to death colour, shared by every particle with
+    /// those colours
+    ramps: Vec<[Handle<StandardMaterial>; COLOR_STEPS]>,
+    /// Where each ramp is in `ramps`, by its colours and glow
+    ramp_index: HashMap<[u32; 9], usize>,
+}
+
+/// Hands out particles, reusing dead ones before spawning new ones.
+#[derive(SystemParam)]
+struct ParticleSpawner<'w, 's> {
+    commands: Commands<'w, 's>,
+    pool: ResMut<'w, ParticlePool>,
+    materials: ResMut<'w, Assets<StandardMaterial>>,
+    particle_query: Query<
+        'w,
+        's,
+        (
+            &'static mut Particle,
+            &'static mut Transform,
+            &'static mut Visibility,
+            &'static mut Mesh3d,
+            &'static mut MeshMaterial3d<StandardMaterial>,
+        ),
+    >,
+}
+
+impl ParticleSpawner<'_, '_> {
+    fn emit(
+        &mut self,
+        definition: &EffectDefinition,
+        count: u32,
+        origin: Vec3,
+        direction: Vec3,
+        velocity: Vec3,
+        tint: Option<Color>,
+    ) {
+        let tint = tint.map_or(LinearRgba::WHITE, LinearRgba::from);
+        let ramp = self.ramp(
+            tinted(definition.color.0, tint),
+            tinted(definition.color.1, tint),
+            definition.glow,
+        );
+        let material = self.pool.ramps[ramp][0].clone();
+        let mesh = self.pool.meshes[definition.shape as usize].clone();
+        for _ in 0..count {
+            let heading =
+                (direction + random_direction() * definition.spread).normalize_or(direction);
+            let particle = Particle {
+                alive: true,
+                velocity: heading * between(definition.speed)
+                    + velocity * definition.inherit_velocity,
+                age: 0.0,
+                lifetime: between(definition.lifetime).max(0.01),
+                gravity: definition.gravity,
+                drag: definition.drag,
+                size: definition.size,
+                ramp,
+                step: 0,
+            };
+            let transform = Transform::from_translation(
+                origin + random_direction() * definition.radius * fastrand::f32(),
+            )
+            .with_scale(Vec3::splat(definition.size.0));
+
+            if let Some(entity) = self.pool.free.pop() {
+                let Ok((
+                    mut slot,
+                    mut slot_transform,
+                    mut visibility,
+                    mut slot_mesh,
+                    mut slot_material,
+                )) = self.particle_query.get_mut(entity)
+                else {
+                    // Despawned by someone else, it's not coming back
+                    self.pool.total -= 1;
+                    continue;
+                };
+                *slot = particle;
+                *slot_transform = transform;
+                *visibility = Visibility::Visible;
+                slot_mesh.0 = mesh.clone();
+                slot_material.0 = material.clone();
+            } else if self.pool.total < MAX_PARTICLES {
+                self.pool.total += 1;
+                self.commands.spawn(pooled_particle(
+                    particle,
+                    transform,
+                    mesh.clone(),
+                    material.clone(),
+                ));
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Finds or makes the materials for fading from `birth` to `death`.
+    fn ramp(&mut self, birth: LinearRgba, death: LinearRgba, glow: bool) -> usize {
+        let mut key = [glow as u32; 9];
+        let components = [
+            birth.red,
+            birth.green,
+            birth.blue,
+            birth.alpha,
+            death.red,
+            death.green,
+            death.blue,
+            death.alpha,
+        ];
+        for (slot, component) in key.iter_mut().zip(components) {
+            *slot = component.to_bits();
+        }
+        if let Some(&index) = self.pool.ramp_index.get(&key) {
+            return index;
+        }
+        let ramp = std::array::from_fn(|step| {
+            let color = birth.mix(&death, step as f32 / (COLOR_STEPS - 1) as f32);
+            self.materials.add(particle_material(color, glow))
+        });
+        let index = self.pool.ramps.len();
+        self.pool.ramps.push(ramp);
+        self.pool.ramp_index.insert(key, index);
+        index
+    }
+}
+
+fn pooled_particle(
+    particle: Particle,
+    transform: Transform,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+) -> impl Bundle {
+    (
+        Name::new("Particle"),
+        particle,
+        transform,
+        Visibility::Visible,
+        Mesh3d(mesh),
+        MeshMaterial3d(material),
+        NotShadowCaster,
+        NotShadowReceiver,
+    )
+}
+
+fn particle_material(color: LinearRgba, glow: bool) -> StandardMaterial {
+    StandardMaterial {
+        base_color: color.into(),
+        unlit: glow,
+        alpha_mode: if glow {
+            AlphaMode::Add
+        } else {
+            AlphaMode::Blend
+        },
+        ..default()
+    }
+}
+
+fn tinted([red, green, blue, alpha]: [f32; 4], tint: LinearRgba) -> LinearRgba {
+    LinearRgba::new(
+        red * tint.red,
+        green * tint.green,
+        blue * tint.blue,
+        alpha * tint.alpha,
+    )
+}
+
+fn between((min, max): (f32, f32)) -> f32 {
+    min + (max - min) * fastrand::f32()
+}
+
+/// Evenly spread over a sphere.
+fn random_direction() -> Vec3 {
+    let y = fastrand::f32() * 2.0 - 1.0;
+    let angle = fastrand::f32() * std::f32::consts::TAU;
+    let ring = (1.0 - y * y).sqrt();
+    Vec3::new(angle.cos() * ring, y, angle.sin() * ring)
+}
+
+fn prewarm_particles(
+    mut commands: Commands,
+    pool: Option<Res<ParticlePool>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // The pool sticks around between levels, there's nothing to redo
+    if pool.is_some() {
+        return;
+    }
+    let material = materials.add(StandardMaterial::default());
+    let meshes = [
+        meshes.add(Sphere::new(0.5)),
+        meshes.add(Cuboid::from_length(1.0)),
+        meshes.add(Capsule3d::new(0.5, 1.0)),
+    ];
+    let free = (0..PREWARMED_PARTICLES)
+        .map(|_| {
+            commands
+                .spawn(pooled_particle(
+                    Particle::default(),
+                    Transform::default(),
+                    meshes[0].clone(),
+                    material.clone(),
+                ))
+                .insert(Visibility::Hidden)
+                .id()
+        })
+        .collect();
+    commands.insert_resource(ParticlePool {
+        free,
+        total: PREWARMED_PARTICLES,
+        meshes,
+        ramps: Vec::new(),
+        ramp_index: HashMap::new(),
+    });
+}
+
+fn fireball_trails(
+    mut commands: Commands,
+    fireball_query: Query<(Entity, &Fireball), Added<Fireball>>,
+) {
+    for (entity, fireball) in &fireball_query {
+        // Might already have hit something and gone
+        commands
+            .entity(entity)
+            .try_insert(ParticleEmitter::new("fireball_trail").with_tint(fireball.element.color()));
+    }
+}
+
+fn fireball_impacts(
+    mut impacts: EventReader<FireballImpact>,
+    mut effects: EventWriter<PlayEffect>,
+) {
+    for impact in impacts.read() {
+        effects.write(
+            PlayEffect::at("fireball_impact", impact.position).with_tint(impact.element.color()),
+        );
+    }
+}
+
+fn dash_afterimages(mut commands: Commands, mut dashes: EventReader<DashStarted>) {
+    for DashStarted(entity) in dashes.read() {
+        commands.entity(*entity).try_insert(
+            ParticleEmitter::new("dash_afterimage").for_seconds(DASH_AFTERIMAGE_SECONDS),
+        );
+    }
+}
+
+fn spike_sparks(mut spike_hits: EventReader<SpikeHit>, mut effects: EventWriter<PlayEffect>) {
+    for SpikeHit(position) in spike_hits.read() {
+        effects.write(PlayEffect::at("spike_sparks", *position));
+    }
+}
+
+fn death_bursts(mut killed: EventReader<EnemyKilled>, mut effects: EventWriter<PlayEffect>) {
+    for kill in killed.read() {
+        effects.write(PlayEffect::at("death_burst", kill.position));
+    }
+}
+
+fn emit_particles(
+    time: Res<Time>,
+    mut effects: EventReader<PlayEffect>,
+    library: Res<EffectLibrary>,
+    definitions: Res<Assets<EffectDefinition>>,
+    mut spawner: ParticleSpawner,
+    mut emitter_query: Query<(
+        Entity,
+        &mut ParticleEmitter,
+        &GlobalTransform,
+        Option<&LinearVelocity>,
+    )>,
+) {
+    for effect in effects.read() {
+        let Some(definition) = library.get(&definitions, effect.effect) else {
+            warn!("Unknown effect {}", effect.effect);
+            continue;
+        };
+        spawner.emit(
+            definition,
+            definition.burst,
+            effect.position,
+            effect.direction,
+            Vec3::ZERO,
+            effect.tint,
+        );
+    }
+
+    for (entity, mut emitter, transform, velocity) in &mut emitter_query {
+        let expired = emitter
+            .duration
+            .as_mut()
+            .is_some_and(|duration| duration.tick(time.delta()).finished());
+        if expired {
+            spawner
+                .commands
+                .entity(entity)
+                .try_remove::<ParticleEmitter>();
+            continue;
+        }
+        let Some(definition) = library.get(&definitions, &emitter.effect) else {
+            continue;
+        };
+        emitter.owed += definition.rate * time.delta_secs();
+        let count = emitter.owed as u32;
+        emitter.owed -= count as f32;
+        spawner.emit(
+            definition,
+            count,
+            transform.translation(),
+            *transform.up(),
+            velocity.map_or(Vec3::ZERO, |velocity| velocity.0),
+            emitter.tint,
+        );
+    }
+}
+
+fn simulate_particles(
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut particle_query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Visibility,
+        &mut MeshMaterial3d<StandardMaterial>,
+    )>,
+) {
+    let delta = time.delta_secs();
+    for (entity, mut particle, mut transform, mut visibility, mut material) in &mut particle_query {
+        if !particle.alive {
+            continue;
+        }
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            particle.alive = false;
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
+            continue;
+        }
+        let slowdown = (1.0 - particle.drag * delta).max(0.0);
+        particle.velocity = particle.velocity * slowdown + Vec3::NEG_Y * particle.gravity * delta;
+        transform.translation += particle.velocity * delta;
+
+        let progress = particle.age / particle.lifetime;
+        transform.scale = Vec3::splat(particle.size.0.lerp(particle.size.1, progress));
+        // Only swaps handles when it reaches the next colour, nothing gets re-uploaded
+        let step = (progress * (COLOR_STEPS - 1) as f32).round() as usize;
+        if step != particle.step {
+            particle.step = step;
+            material.0 = pool.ramps[particle.ramp][step].clone();
+        }
+    }
+}
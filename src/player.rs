@@ -1,16 +1,32 @@
 use avian3d::prelude::*;
-use bevy::{color::palettes::css, input::mouse::MouseMotion, prelude::*};
-use bevy_panorbit_camera::PanOrbitCamera;
-use bevy_tnua::{
-    builtins::{TnuaBuiltinCrouch, TnuaBuiltinDash},
-    prelude::*,
-};
+use bevy::{color::palettes::css, prelude::*};
+use bevy_tnua::{builtins::TnuaBuiltinCrouch, prelude::*};
 use bevy_tnua_avian3d::*;
 
 use crate::GameState;
+use crate::camera::ThirdPersonCamera;
 
 use crate::SpikeDamageCooldown;
+use crate::animation::{CastReleased, CastStarted, CharacterRig, start_casts};
+use crate::gameplay::attacks::aim::{AimTarget, CastPoint};
 use crate::gameplay::attacks::fireball::spawn_fireball;
+use crate::gameplay::dialogue::ActiveDialogue;
+use crate::gameplay::elements::{Element, Resistances};
+use crate::gameplay::items::{Equipment, Inventory, InventoryOpen};
+use crate::gameplay::kill_volumes::RespawnPoint;
+use crate::gameplay::progression::{Progression, SkillTreeOpen};
+use crate::gameplay::stats::{Stat, Stats};
+use crate::gameplay::status_effects::Stunned;
+use crate::gameplay::traversal::TraversalAbilities;
+use crate::gameplay::vitals::{Mana, Stamina};
+use crate::gameplay::water::{Breath, Swimming};
+use crate::map::MapOpen;
+
+pub const PLAYER_MAX_HEALTH: f32 = 100.0;
+/// Mana each fireball costs
+pub(crate) const FIREBALL_MANA_COST: f32 = 12.0;
+/// Seconds between casts
+const CAST_COOLDOWN: f32 = 0.4;
 
 #[derive(Component, Reflect)]
 #[reflect(Component)]
@@ -20,18 +36,84 @@ pub struct Player;
 #[reflect(Component)]
 pub struct Health(pub f32);
 
+/// What healing tops `Health` up to.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct MaxHealth(pub f32);
+
+/// How the character decides which way to face.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum MovementMode {
+    /// Move relative to the camera and turn toward the direction of travel
+    #[default]
+    FreeRun,
+    /// Always face where the camera looks, walking sideways and backwards
+    Strafe,
+}
+
+/// What the next cast is made of. Cycled with Q or gamepad North.
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct SelectedElement(pub Element);
+
+impl Default for SelectedElement {
+    fn default() -> Self {
+        Self(Element::Fire)
+    }
+}
+
+impl SelectedElement {
+    fn next(self) -> Self {
+        Self(match self.0 {
+            Element::Fire => Element::Ice,
+            Element::Ice => Element::Lightning,
+            _ => Element::Fire,
+        })
+    }
+}
+
+/// Time until the player can cast again.
+#[derive(Component)]
+pub struct CastCooldown(pub Timer);
+
+impl Default for CastCooldown {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(CAST_COOLDOWN, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self(timer)
+    }
+}
+
+/// Keeps the player strafing for a moment after casting so attacks don't spin them around.
+#[derive(Component)]
+pub struct AttackStance(pub Timer);
+
+impl Default for AttackStance {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(0.6, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self(timer)
+    }
+}
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<(Player, Health)>()
+        app.register_type::<(Player, Health, MaxHealth, MovementMode, SelectedElement)>()
             .add_systems(OnEnter(GameState::InGame), setup_player)
             .add_systems(
                 FixedUpdate,
-                (apply_controls, cam_follow_and_face, always_orbit_camera)
-                    .chain()
+                apply_controls
                     .in_set(TnuaUserControlsSystemSet)
                     .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                release_fireball
+                    .after(start_casts)
+                    .run_if(in_state(GameState::InGame)),
             );
     }
 }
@@ -41,13 +123,14 @@ fn setup_player(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
+    let spawn_pos = Vec3::new(0.0, 2.0, 0.0);
     commands.spawn((
         Mesh3d(meshes.add(Capsule3d {
             radius: 0.5,
             half_length: 0.5,
         })),
         MeshMaterial3d(materials.add(Color::from(css::DARK_GOLDENROD))),
-        Transform::from_xyz(0.0, 2.0, 0.0),
+        Transform::from_translation(spawn_pos),
         // The player character needs to be configured as a dynamic rigid body of the physics
         // engine.
         RigidBody::Dynamic,
@@ -57,59 +140,139 @@ fn setup_player(
         // A sensor shape is not strictly necessary, but without it we'll get weird results.
         TnuaAvian3dSensorShape(Collider::cylinder(0.49, 0.0)),
         // Tnua can fix the rotation, but the character will still get rotated before it can do so.
-        // By locking the rotation we can prevent this.
-        LockedAxes::ROTATION_LOCKED,
+        // By locking the rotation we can prevent this. Yaw stays free so Tnua can turn us.
+        LockedAxes::ROTATION_LOCKED.unlock_rotation_y(),
         CollisionEventsEnabled,
-        Player,
-        Health(100.0),
-        SpikeDamageCooldown(Timer::from_seconds(1.0, TimerMode::Once)),
+        (
+            Player,
+            Health(PLAYER_MAX_HEALTH),
+            MovementMode::default(),
+            TraversalAbilities::default(),
+            Breath::default(),
+            Inventory::default(),
+            Equipment::default(),
+            Progression::default(),
+            Stats::new([
+                (Stat::WalkSpeed, 10.0),
+                (Stat::JumpHeight, 4.0),
+                (Stat::DashDistance, 20.0),
+                (Stat::FireballDamage, 25.0),
+                (Stat::FireballSpeed, 20.0),
+                (Stat::DamageTaken, 1.0),
+            ]),
+            Resistances::default(),
+            SelectedElement::default(),
+            AttackStance::default(),
+            RespawnPoint::new(spawn_pos),
+            CharacterRig::new("characters/player.gltf"),
+            SpikeDamageCooldown(Timer::from_seconds(1.0, TimerMode::Once)),
+        ),
+        (
+            MaxHealth(PLAYER_MAX_HEALTH),
+            Mana::default(),
+            Stamina::default(),
+            CastCooldown::default(),
+        ),
+        // Right hand, fireballs come out of here
+        children![(CastPoint, Transform::from_xyz(0.5, 0.4, -0.7))],
     ));
 }
 
-fn apply_controls(
+pub(crate) fn apply_controls(
+    time: Res<Time>,
     keyboard: Res<ButtonInput<KeyCode>>,
     mouse: Res<ButtonInput<MouseButton>>,
-    mut query: Query<&mut TnuaController, With<Player>>,
-    tfm_q: Query<&Transform, With<Player>>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    gamepads: Query<&Gamepad>,
+    mut cast_started: EventWriter<CastStarted>,
+    inventory_open: Res<InventoryOpen>,
+    skill_tree_open: Res<SkillTreeOpen>,
+    map_open: Res<MapOpen>,
+    dialogue: Res<ActiveDialogue>,
+    mut query: Query<
+        (
+            Entity,
+            &mut TnuaController,
+            &mut MovementMode,
+            &mut SelectedElement,
+            &mut AttackStance,
+            &mut CastCooldown,
+            &mut Mana,
+            &Stats,
+            Has<Swimming>,
+            Has<Stunned>,
+        ),
+        With<Player>,
+    >,
+    camera_q: Query<&ThirdPersonCamera>,
 ) {
-    let Ok(mut controller) = query.single_mut() else {
+    let Ok((
+        entity,
+        mut controller,
+        mut mode,
+        mut element,
+        mut stance,
+        mut cooldown,
+        mut mana,
+        stats,
+        swimming,
+        stunned,
+    )) = query.single_mut()
+    else {
         return;
     };
-    let Ok(transform) = tfm_q.single() else {
+    let Ok(camera) = camera_q.single() else {
         return;
     };
+    stance.0.tick(time.delta());
+    cooldown.0.tick(time.delta());
 
-    // --- ATTACK ---
-    if mouse.just_pressed(MouseButton::Left) {
-        let fireball_speed = 20.0;
-        let fireball_damage = 25.0;
-        let spawn_pos = transform.translation + transform.forward() * 1.5;
-        let fireball_dir = transform.forward();
+    // Stand there and wait it out, or listen
+    if stunned || dialogue.0.is_some() {
+        controller.basis(TnuaBuiltinWalk {
+            float_height: 1.5,
+            ..Default::default()
+        });
+        return;
+    }
 
-        spawn_fireball(
-            &mut commands,
-            &mut meshes,
-            &mut materials,
-            spawn_pos,
-            fireball_dir,
-            fireball_damage,
-            fireball_speed,
-        );
+    if keyboard.just_pressed(KeyCode::KeyV) {
+        *mode = match *mode {
+            MovementMode::FreeRun => MovementMode::Strafe,
+            MovementMode::Strafe => MovementMode::FreeRun,
+        };
     }
 
-    // --- JUMP ---
-    if keyboard.pressed(KeyCode::Space) {
-        controller.action(TnuaBuiltinJump {
-            height: 4.0,
-            ..Default::default()
-        });
+    // Movement is relative to where the camera looks, not where the body faces
+    let camera_yaw = Quat::from_rotation_y(camera.yaw);
+
+    let gamepad = gamepads.iter().next();
+    let pad_pressed = |button| gamepad.is_some_and(|pad| pad.pressed(button));
+    let pad_just_pressed = |button| gamepad.is_some_and(|pad| pad.just_pressed(button));
+
+    // --- ATTACK ---
+    // The fireball itself comes out in release_fireball, once the cast animation gets there
+    let attack_pressed =
+        mouse.just_pressed(MouseButton::Left) || pad_just_pressed(GamepadButton::RightTrigger2);
+    if keyboard.just_pressed(KeyCode::KeyQ) || pad_just_pressed(GamepadButton::North) {
+        *element = element.next();
     }
+    if attack_pressed
+        && !inventory_open.0
+        && !skill_tree_open.0
+        && !map_open.0
+        && cooldown.0.finished()
+        && mana.0.try_spend(FIREBALL_MANA_COST)
+    {
+        cast_started.write(CastStarted(entity));
+        stance.0.reset();
+        cooldown.0.reset();
+    }
+
+    // Jumping and dashing live in gameplay::traversal
 
     // --- CROUCH ---
-    if keyboard.pressed(KeyCode::ControlLeft) {
+    // Doubles as "dive" while swimming
+    if !swimming && (keyboard.pressed(KeyCode::ControlLeft) || pad_pressed(GamepadButton::East)) {
         controller.action(TnuaBuiltinCrouch {
             float_offset: -0.5,
             ..Default::default()
@@ -119,77 +282,74 @@ fn apply_controls(
     // --- WALK/RUN ---
     let mut direction = Vec3::ZERO;
     if keyboard.pressed(KeyCode::KeyS) {
-        direction += transform.rotation * Vec3::Z;
+        direction += camera_yaw * Vec3::Z;
     }
     if keyboard.pressed(KeyCode::KeyW) {
-        direction -= transform.rotation * Vec3::Z;
+        direction -= camera_yaw * Vec3::Z;
     }
     if keyboard.pressed(KeyCode::KeyA) {
-        direction -= transform.rotation * Vec3::X;
+        direction -= camera_yaw * Vec3::X;
     }
     if keyboard.pressed(KeyCode::KeyD) {
-        direction += transform.rotation * Vec3::X;
+        direction += camera_yaw * Vec3::X;
     }
-
-    //---DASH
-    if keyboard.pressed(KeyCode::ShiftLeft) {
-        // Dash in the facing direction
-        controller.action(TnuaBuiltinDash {
-            //desired_forward: dash_dir,
-            displacement: direction.normalize_or_zero() * 20.0,
-            ..Default::default()
-        });
+    if let Some(stick) = gamepad.map(|pad| pad.left_stick()) {
+        direction += camera_yaw * Vec3::new(stick.x, 0.0, -stick.y);
     }
+    let direction = direction.clamp_length_max(1.0);
+
+    // Holding right mouse or having just cast keeps us aiming
+    let strafing = *mode == MovementMode::Strafe
+        || mouse.pressed(MouseButton::Right)
+        || pad_pressed(GamepadButton::LeftTrigger2)
+        || !stance.0.finished();
+    let desired_forward = if strafing {
+        Dir3::new(camera_yaw * Vec3::NEG_Z).ok()
+    } else {
+        Dir3::new(direction).ok()
+    };
 
     controller.basis(TnuaBuiltinWalk {
-        desired_velocity: direction.normalize_or_zero() * 10.0,
+        desired_velocity: direction * stats.get(Stat::WalkSpeed),
+        desired_forward,
         float_height: 1.5,
         ..Default::default()
     });
 }
 
-fn always_orbit_camera(
-    mut panorbit_query: Query<&mut PanOrbitCamera>,
-    mut mouse_motion_events: EventReader<MouseMotion>,
+fn release_fireball(
+    mut cast_released: EventReader<CastReleased>,
+    aim: Res<AimTarget>,
+    player_query: Query<(&Transform, &Stats, &SelectedElement), With<Player>>,
+    cast_point_q: Query<&GlobalTransform, With<CastPoint>>,
+    camera_q: Query<&GlobalTransform, With<ThirdPersonCamera>>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    let mut delta = Vec2::ZERO;
-    for event in mouse_motion_events.read() {
-        delta += event.delta;
-    }
-    if delta != Vec2::ZERO {
-        for mut cam in &mut panorbit_query {
-            cam.target_yaw -= delta.x * 0.005;
-            cam.target_pitch += delta.y * 0.005;
-
-            // Clamp pitch (in radians). Example: -1.5 to 1.5 (~-86 to +86 degrees)
-            let min_pitch = -0.25;
-            let max_pitch = 1.5;
-            cam.target_pitch = cam.target_pitch.clamp(min_pitch, max_pitch);
-
-            cam.force_update = true;
-        }
-    }
-}
+    let Ok(camera_transform) = camera_q.single() else {
+        return;
+    };
+    for CastReleased(caster) in cast_released.read() {
+        let Ok((transform, stats, element)) = player_query.get(*caster) else {
+            continue;
+        };
+        let spawn_pos = cast_point_q
+            .single()
+            .map(|hand| hand.translation())
+            .unwrap_or(transform.translation + transform.forward() * 1.5);
+        // From the hand to whatever's under the crosshair, so pitch counts
+        let fireball_dir = aim.direction_from(spawn_pos, camera_transform.forward());
 
-fn cam_follow_and_face(
-    mut pan_orbit_q: Query<&mut PanOrbitCamera>,
-    mut player_q: Query<&mut Transform, With<Player>>,
-) {
-    if let (Ok(mut pan_orbit), Ok(mut player_tfm)) =
-        (pan_orbit_q.single_mut(), player_q.single_mut())
-    {
-        // Smoothly follow player
-        let smoothing = 0.15;
-        pan_orbit.target_focus = pan_orbit
-            .target_focus
-            .lerp(player_tfm.translation, smoothing);
-        pan_orbit.force_update = true;
-
-        // Rotate player to match camera yaw (around Y axis)
-        if let Some(yaw) = pan_orbit.yaw {
-            player_tfm.rotation = Quat::from_rotation_y(yaw);
-        } else {
-            player_tfm.rotation = Quat::from_rotation_y(pan_orbit.target_yaw);
-        }
+        spawn_fireball(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            spawn_pos,
+            fireball_dir,
+            stats.get(Stat::FireballDamage),
+            stats.get(Stat::FireballSpeed),
+            element.0,
+        );
     }
 }
@@ -0,0 +1,57 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::progression::Progression;
+use crate::gameplay::quests::QuestLog;
+
+/// Next to the executable's working directory, not in assets/ where the watcher would see it
+const SAVE_PATH: &str = "save.ron";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(read_save())
+            .add_event::<SaveGame>()
+            .add_systems(Last, write_save.run_if(on_event::<SaveGame>));
+    }
+}
+
+/// Everything that survives quitting the game. Systems keep their part of it up to
+/// date and send `SaveGame` when it's worth writing out.
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct SaveData {
+    pub progression: Option<Progression>,
+    /// Map cells the player has uncovered in each level, see `ExploredMap` and `CurrentLevel`
+    pub explored_by_level: HashMap<String, HashSet<(i32, i32)>>,
+    /// Quest log for each level, see `CurrentLevel`
+    pub quests_by_level: HashMap<String, QuestLog>,
+}
+
+#[derive(Event)]
+pub struct SaveGame;
+
+fn read_save() -> SaveData {
+    let Ok(contents) = std::fs::read_to_string(SAVE_PATH) else {
+        return SaveData::default();
+    };
+    ron::from_str(&contents).unwrap_or_else(|error| {
+        warn!("Ignoring unreadable save file {SAVE_PATH}: {error}");
+        SaveData::default()
+    })
+}
+
+fn write_save(mut events: EventReader<SaveGame>, save: Res<SaveData>) {
+    events.clear();
+    let result = ron::ser::to_string_pretty(&*save, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| {
+            std::fs::write(SAVE_PATH, contents).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        warn!("Couldn't write {SAVE_PATH}: {error}");
+    }
+}
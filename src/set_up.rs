@@ -1,25 +1,38 @@
 use bevy::prelude::*;
-use bevy_panorbit_camera::PanOrbitCamera;
 
 use crate::GameState;
-use crate::gameplay::moving_platforms::PlatformWaypoint; // Import your GameState
+use crate::camera::ThirdPersonCamera;
+use crate::gameplay::moving_platforms::{
+    PlatformValidation, PlatformValidationSet, PlatformWaypoint,
+};
 
 //use crate::dev_utils::debug_print_game_state;
 
+/// The level everything gets loaded from
+const LEVEL_PATH: &str = "Untitled.gltf";
+
 #[derive(Resource)]
 pub struct SceneHandle(pub Handle<Scene>);
 
+/// Which level is being played. Saved state that belongs to one level (explored map,
+/// finished quests) is filed under this, generated levels should put their seed in it.
+#[derive(Resource, Default, Clone, PartialEq, Eq, Debug)]
+pub struct CurrentLevel(pub String);
+
 pub struct SetupPlugin;
 impl Plugin for SetupPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_camera_and_lights)
+        app.init_resource::<CurrentLevel>()
+            .add_systems(Startup, setup_camera_and_lights)
             .add_systems(
                 OnEnter(GameState::Loading),
                 (load_scene, spawn_scene).chain(), // Ensures load_scene runs before spawn_scene
             )
             .add_systems(
                 Update,
-                check_scene_loaded.run_if(in_state(GameState::Loading)),
+                check_scene_loaded
+                    .after(PlatformValidationSet)
+                    .run_if(in_state(GameState::Loading)),
             );
     }
 }
@@ -27,13 +40,7 @@ impl Plugin for SetupPlugin {
 fn setup_camera_and_lights(mut commands: Commands) {
     commands.spawn((
         Transform::from_translation(Vec3::new(0.0, 1.5, 5.0)),
-        PanOrbitCamera {
-            pan_sensitivity: 0.0,
-            pan_smoothness: 0.0,
-            orbit_sensitivity: 0.0,
-            zoom_sensitivity: 0.0,
-            ..default()
-        },
+        ThirdPersonCamera::default(),
     ));
 
     commands.spawn((PointLight::default(), Transform::from_xyz(5.0, 5.0, 5.0)));
@@ -49,8 +56,9 @@ fn setup_camera_and_lights(mut commands: Commands) {
 }
 
 fn load_scene(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let handle = asset_server.load(GltfAssetLabel::Scene(0).from_asset("Untitled.gltf"));
+    let handle = asset_server.load(GltfAssetLabel::Scene(0).from_asset(LEVEL_PATH));
     commands.insert_resource(SceneHandle(handle));
+    commands.insert_resource(CurrentLevel(LEVEL_PATH.to_string()));
 }
 
 fn spawn_scene(mut commands: Commands, scene_handle: Res<SceneHandle>) {
@@ -61,11 +69,14 @@ fn check_scene_loaded(
     mut next_state: ResMut<NextState<GameState>>,
     scene_handle: Option<Res<SceneHandle>>,
     asset_server: Res<AssetServer>,
+    validation: Res<PlatformValidation>,
     waypoint_query: Query<&PlatformWaypoint>,
 ) {
-    if let Some(handle) = scene_handle {
-        if asset_server.is_loaded(&handle.0) && !waypoint_query.is_empty() {
-            next_state.set(GameState::InGame);
-        }
+    if let Some(handle) = scene_handle
+        && asset_server.is_loaded(&handle.0)
+        && !waypoint_query.is_empty()
+        && validation.passed()
+    {
+        next_state.set(GameState::InGame);
     }
 }
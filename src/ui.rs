@@ -2,95 +2,724 @@ use bevy::{color::palettes::css, prelude::*};
 
 use crate::{
     GameState,
-    player::{Health, Player},
+    gameplay::dialogue::{
+        ActiveDialogue, ChooseDialogueOption, DialogueDefinition, DialogueLibrary, Npc,
+    },
+    gameplay::items::{Equipment, Inventory, InventoryOpen, ItemDefinition, ItemLibrary, UseItem},
+    gameplay::progression::{LearnSkill, PlayerSkillTree, Progression, SkillTree, SkillTreeOpen},
+    gameplay::quests::{QuestDefinition, QuestLibrary, QuestLog, QuestLogOpen},
+    gameplay::{
+        attacks::aim::AimTarget,
+        enemies::melee_creep::{Dead, Enemy},
+    },
+    player::Player,
 };
 
 pub struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::InGame), spawn_health_bar);
+        app.add_systems(
+            OnEnter(GameState::InGame),
+            (
+                spawn_crosshair,
+                spawn_inventory_panel,
+                spawn_skill_tree_panel,
+                spawn_quest_log_panel,
+                spawn_dialogue_box,
+            ),
+        );
         app.add_systems(
             Update,
-            update_health_bar.run_if(in_state(GameState::InGame)),
+            (
+                update_crosshair,
+                (
+                    toggle_inventory,
+                    update_inventory_panel,
+                    click_inventory_slots,
+                )
+                    .chain(),
+                (
+                    toggle_skill_tree,
+                    update_skill_tree_panel,
+                    click_skill_nodes,
+                )
+                    .chain(),
+                (toggle_quest_log, update_quest_log_panel).chain(),
+                (update_dialogue_box, click_dialogue_choices).chain(),
+            )
+                .run_if(in_state(GameState::InGame)),
         );
     }
 }
 
 #[derive(Component)]
-struct HealthBarFill;
+struct Crosshair;
+
+fn spawn_crosshair(mut commands: Commands) {
+    // Full-screen wrapper so the dot sits exactly in the middle at any resolution
+    commands
+        .spawn(Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_child((
+            Node {
+                width: Val::Px(6.0),
+                height: Val::Px(6.0),
+                ..default()
+            },
+            BorderRadius::MAX,
+            BackgroundColor(Color::from(css::WHITE)),
+            Crosshair,
+        ));
+}
+
+fn update_crosshair(
+    aim: Res<AimTarget>,
+    enemy_query: Query<(), (With<Enemy>, Without<Dead>)>,
+    mut crosshair_query: Query<&mut BackgroundColor, With<Crosshair>>,
+) {
+    let on_enemy = aim
+        .entity
+        .is_some_and(|entity| enemy_query.contains(entity));
+    for mut color in &mut crosshair_query {
+        color.0 = if on_enemy {
+            Color::from(css::RED)
+        } else {
+            Color::from(css::WHITE)
+        };
+    }
+}
+
+#[derive(Component)]
+struct InventoryPanel;
+
+/// Where the slot buttons go, rebuilt whenever the inventory changes
+#[derive(Component)]
+struct InventorySlots;
 
 #[derive(Component)]
-struct HealthBarText;
+struct InventorySummary;
+
+#[derive(Component)]
+struct InventorySlotButton(usize);
+
+/// Description of whichever slot the mouse is over
+#[derive(Component)]
+struct InventoryTooltip;
+
+fn spawn_inventory_panel(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Px(420.0),
+            position_type: PositionType::Absolute,
+            right: Val::Px(40.0),
+            top: Val::Px(40.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(12.0)),
+            row_gap: Val::Px(8.0),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.8)),
+        Visibility::Hidden,
+        InventoryPanel,
+        children![
+            Text::new("Inventory"),
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                InventorySummary,
+            ),
+            (
+                Node {
+                    flex_wrap: FlexWrap::Wrap,
+                    column_gap: Val::Px(6.0),
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                InventorySlots,
+            ),
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+                TextColor(Color::from(css::LIGHT_GRAY)),
+                InventoryTooltip,
+            ),
+        ],
+    ));
+}
+
+fn toggle_inventory(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut open: ResMut<InventoryOpen>,
+    mut panel_query: Query<&mut Visibility, With<InventoryPanel>>,
+) {
+    let pressed = keyboard.just_pressed(KeyCode::KeyI)
+        || gamepads
+            .iter()
+            .any(|pad| pad.just_pressed(GamepadButton::Select));
+    if !pressed {
+        return;
+    }
+    open.0 = !open.0;
+    for mut visibility in &mut panel_query {
+        *visibility = if open.0 {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn update_inventory_panel(
+    mut commands: Commands,
+    library: Option<Res<ItemLibrary>>,
+    definitions: Res<Assets<ItemDefinition>>,
+    player_query: Query<(Ref<Inventory>, Ref<Equipment>), With<Player>>,
+    slots_query: Query<Entity, With<InventorySlots>>,
+    mut summary_query: Query<&mut Text, With<InventorySummary>>,
+) {
+    let (Some(library), Ok((inventory, equipment))) = (library, player_query.single()) else {
+        return;
+    };
+    // Definitions load in after the player spawns, so redraw when they do too
+    if !inventory.is_changed() && !equipment.is_changed() && !library.is_changed() {
+        return;
+    }
+    let name = |id: &str| {
+        library
+            .get(&definitions, id)
+            .map_or(id.to_string(), |definition| definition.name.clone())
+    };
 
-fn spawn_health_bar(mut commands: Commands) {
-    // Parent node (background)
-    let parent = commands
-        .spawn((
+    if let Ok(mut text) = summary_query.single_mut() {
+        let mut equipped: Vec<_> = equipment
+            .slots
+            .iter()
+            .map(|(slot, item)| format!("{slot:?}: {}", name(item)))
+            .collect();
+        equipped.sort();
+        text.0 = format!(
+            "Gold: {}\n{}",
+            inventory.currency,
+            if equipped.is_empty() {
+                "Nothing equipped".to_string()
+            } else {
+                equipped.join("\n")
+            }
+        );
+    }
+
+    let Ok(slots) = slots_query.single() else {
+        return;
+    };
+    commands.entity(slots).despawn_related::<Children>();
+    for index in 0..inventory.capacity {
+        let label = inventory
+            .slots
+            .get(index)
+            .map(|stack| match stack.count {
+                1 => name(&stack.item),
+                count => format!("{} x{count}", name(&stack.item)),
+            })
+            .unwrap_or_default();
+        commands.entity(slots).with_child((
+            Button,
             Node {
-                width: Val::Px(200.0),
-                height: Val::Px(24.0),
-                position_type: PositionType::Absolute,
-                left: Val::Px(200.0), // Move bar horizontally
-                top: Val::Px(200.0),  // Move bar vertically
+                width: Val::Px(92.0),
+                height: Val::Px(48.0),
+                padding: UiRect::all(Val::Px(4.0)),
                 ..default()
             },
-            BackgroundColor(Color::from(css::DARK_GRAY)),
-        ))
-        .id();
+            BackgroundColor(Color::from(css::DIM_GRAY)),
+            InventorySlotButton(index),
+            children![(
+                Text::new(label),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+            )],
+        ));
+    }
+}
+
+fn click_inventory_slots(
+    open: Res<InventoryOpen>,
+    library: Option<Res<ItemLibrary>>,
+    definitions: Res<Assets<ItemDefinition>>,
+    mut use_item: EventWriter<UseItem>,
+    inventory_query: Query<&Inventory, With<Player>>,
+    mut tooltip_query: Query<&mut Text, With<InventoryTooltip>>,
+    mut button_query: Query<
+        (&Interaction, &InventorySlotButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, button, mut color) in &mut button_query {
+        color.0 = match interaction {
+            Interaction::Pressed => Color::from(css::GRAY),
+            Interaction::Hovered => Color::from(css::SLATE_GRAY),
+            Interaction::None => Color::from(css::DIM_GRAY),
+        };
+        if open.0 && *interaction == Interaction::Pressed {
+            use_item.write(UseItem { slot: button.0 });
+        }
+        if *interaction == Interaction::Hovered
+            && let Ok(mut tooltip) = tooltip_query.single_mut()
+        {
+            tooltip.0 = inventory_query
+                .single()
+                .ok()
+                .and_then(|inventory| inventory.slots.get(button.0))
+                .zip(library.as_ref())
+                .and_then(|(stack, library)| library.get(&definitions, &stack.item))
+                .map(|definition| definition.description.clone())
+                .unwrap_or_default();
+        }
+    }
+}
+
+#[derive(Component)]
+struct SkillTreePanel;
+
+#[derive(Component)]
+struct SkillTreeHeader;
+
+#[derive(Component)]
+struct SkillTreeNodes;
+
+#[derive(Component)]
+struct SkillNodeButton(String);
+
+fn spawn_skill_tree_panel(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Px(480.0),
+            position_type: PositionType::Absolute,
+            left: Val::Px(40.0),
+            top: Val::Px(260.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(12.0)),
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.8)),
+        Visibility::Hidden,
+        SkillTreePanel,
+        children![
+            Text::new("Skills"),
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                SkillTreeHeader,
+            ),
+            (
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                SkillTreeNodes,
+            ),
+        ],
+    ));
+}
+
+fn toggle_skill_tree(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut open: ResMut<SkillTreeOpen>,
+    mut panel_query: Query<&mut Visibility, With<SkillTreePanel>>,
+) {
+    let pressed = keyboard.just_pressed(KeyCode::KeyK)
+        || gamepads
+            .iter()
+            .any(|pad| pad.just_pressed(GamepadButton::Start));
+    if !pressed {
+        return;
+    }
+    open.0 = !open.0;
+    for mut visibility in &mut panel_query {
+        *visibility = if open.0 {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn skill_node_color(learned: bool, available: bool) -> Color {
+    if learned {
+        Color::from(css::DARK_GREEN)
+    } else if available {
+        Color::from(css::DIM_GRAY)
+    } else {
+        Color::from(css::DARK_SLATE_GRAY)
+    }
+}
 
-    // Fill node (foreground)
-    let fill = commands
-        .spawn((
+fn update_skill_tree_panel(
+    mut commands: Commands,
+    tree: Res<PlayerSkillTree>,
+    trees: Res<Assets<SkillTree>>,
+    mut tree_events: EventReader<AssetEvent<SkillTree>>,
+    player_query: Query<Ref<Progression>, With<Player>>,
+    nodes_query: Query<Entity, With<SkillTreeNodes>>,
+    mut header_query: Query<&mut Text, With<SkillTreeHeader>>,
+) {
+    let tree_changed = tree_events
+        .read()
+        .any(|event| event.is_loaded_with_dependencies(&tree.0) || event.is_modified(&tree.0));
+    let Ok(progression) = player_query.single() else {
+        return;
+    };
+    if !progression.is_changed() && !tree_changed {
+        return;
+    }
+
+    if let Ok(mut text) = header_query.single_mut() {
+        text.0 = format!(
+            "Level {}   XP {}/{}   Skill points: {}",
+            progression.level,
+            progression.xp,
+            Progression::xp_to_next(progression.level),
+            progression.skill_points
+        );
+    }
+
+    let (Ok(nodes), Some(tree)) = (nodes_query.single(), trees.get(&tree.0)) else {
+        return;
+    };
+    commands.entity(nodes).despawn_related::<Children>();
+    for node in &tree.nodes {
+        let learned = progression.learned(&node.id);
+        let available = tree.can_learn(&progression, &node.id);
+        commands.entity(nodes).with_child((
+            Button,
             Node {
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
+                padding: UiRect::all(Val::Px(4.0)),
                 ..default()
             },
-            BackgroundColor(Color::from(css::GREEN)),
-            HealthBarFill,
-        ))
-        .id();
-
-    // Text node (health value)
-    let text = commands
-        .spawn((
-            Text::new("100"),
+            BackgroundColor(skill_node_color(learned, available)),
+            SkillNodeButton(node.id.clone()),
+            children![(
+                Text::new(format!(
+                    "{} ({})  {}",
+                    node.name, node.cost, node.description
+                )),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+            )],
+        ));
+    }
+}
+
+fn click_skill_nodes(
+    open: Res<SkillTreeOpen>,
+    mut learn: EventWriter<LearnSkill>,
+    button_query: Query<(&Interaction, &SkillNodeButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in &button_query {
+        if open.0 && *interaction == Interaction::Pressed {
+            learn.write(LearnSkill(button.0.clone()));
+        }
+    }
+}
+
+#[derive(Component)]
+struct QuestLogPanel;
+
+/// Where the quest entries go, rebuilt whenever the log changes
+#[derive(Component)]
+struct QuestLogEntries;
+
+fn spawn_quest_log_panel(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Px(460.0),
+            position_type: PositionType::Absolute,
+            left: Val::Percent(35.0),
+            top: Val::Px(80.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(12.0)),
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.8)),
+        Visibility::Hidden,
+        QuestLogPanel,
+        children![
+            Text::new("Quests"),
+            (
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                },
+                QuestLogEntries,
+            ),
+        ],
+    ));
+}
+
+fn toggle_quest_log(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut open: ResMut<QuestLogOpen>,
+    mut panel_query: Query<&mut Visibility, With<QuestLogPanel>>,
+) {
+    let pressed = keyboard.just_pressed(KeyCode::KeyJ)
+        || gamepads
+            .iter()
+            .any(|pad| pad.just_pressed(GamepadButton::DPadRight));
+    if !pressed {
+        return;
+    }
+    open.0 = !open.0;
+    for mut visibility in &mut panel_query {
+        *visibility = if open.0 {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn update_quest_log_panel(
+    mut commands: Commands,
+    library: Res<QuestLibrary>,
+    definitions: Res<Assets<QuestDefinition>>,
+    log: Res<QuestLog>,
+    entries_query: Query<Entity, With<QuestLogEntries>>,
+) {
+    if !log.is_changed() && !library.is_changed() {
+        return;
+    }
+    let Ok(entries) = entries_query.single() else {
+        return;
+    };
+    commands.entity(entries).despawn_related::<Children>();
+
+    for quest in &log.active {
+        let Some(definition) = library.get(&definitions, &quest.id) else {
+            continue;
+        };
+        let objectives: Vec<String> = definition
+            .objectives
+            .iter()
+            .enumerate()
+            .map(|(index, objective)| {
+                let progress = quest.progress.get(index).copied().unwrap_or_default();
+                let target = objective.goal.target();
+                let mark = if progress >= target { "x" } else { " " };
+                format!("[{mark}] {} {progress}/{target}", objective.description)
+            })
+            .collect();
+        commands.entity(entries).with_children(|parent| {
+            parent.spawn(Text::new(definition.name.clone()));
+            parent.spawn((
+                Text::new(format!(
+                    "{}\n{}",
+                    definition.description,
+                    objectives.join("\n")
+                )),
+                TextFont {
+                    font_size: 13.0,
+                    ..default()
+                },
+            ));
+        });
+    }
+
+    // Finished ones stay listed, greyed out
+    for id in &log.completed {
+        let name = library
+            .get(&definitions, id)
+            .map_or(id.clone(), |definition| definition.name.clone());
+        commands.entity(entries).with_child((
+            Text::new(format!("{name} (done)")),
             TextFont {
-                font_size: 100.0,
-                ..default()
-            },
-            TextColor(Color::from(css::WHITE)),
-            TextLayout::new_with_justify(JustifyText::Center),
-            Node {
-                position_type: PositionType::Absolute,
-                bottom: Val::Px(5.0),
-                right: Val::Px(5.0),
+                font_size: 13.0,
                 ..default()
             },
-            HealthBarText,
-        ))
-        .id();
+            TextColor(Color::from(css::GRAY)),
+        ));
+    }
+}
+
+#[derive(Component)]
+struct DialogueBox;
+
+#[derive(Component)]
+struct DialogueSpeaker;
+
+#[derive(Component)]
+struct DialogueText;
+
+/// Where the choice buttons go, rebuilt on every new line
+#[derive(Component)]
+struct DialogueChoices;
 
-    // Add children to parent
-    commands.entity(parent).add_children(&[fill, text]);
+#[derive(Component)]
+struct DialogueChoiceButton(usize);
+
+fn spawn_dialogue_box(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(60.0),
+            position_type: PositionType::Absolute,
+            left: Val::Percent(20.0),
+            bottom: Val::Px(40.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(16.0)),
+            row_gap: Val::Px(8.0),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.85)),
+        Visibility::Hidden,
+        DialogueBox,
+        children![
+            (
+                Text::default(),
+                TextColor(Color::from(css::GOLD)),
+                DialogueSpeaker,
+            ),
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                DialogueText,
+            ),
+            (
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                DialogueChoices,
+            ),
+        ],
+    ));
 }
 
-fn update_health_bar(
-    health_query: Query<&Health, With<Player>>,
-    mut fill_query: Query<&mut Node, With<HealthBarFill>>,
-    mut text_query: Query<&mut Text, With<HealthBarText>>,
+fn update_dialogue_box(
+    mut commands: Commands,
+    active: Res<ActiveDialogue>,
+    library: Res<DialogueLibrary>,
+    definitions: Res<Assets<DialogueDefinition>>,
+    npc_query: Query<&Npc>,
+    mut box_query: Query<&mut Visibility, With<DialogueBox>>,
+    mut speaker_query: Query<&mut Text, (With<DialogueSpeaker>, Without<DialogueText>)>,
+    mut text_query: Query<&mut Text, (With<DialogueText>, Without<DialogueSpeaker>)>,
+    choices_query: Query<Entity, With<DialogueChoices>>,
 ) {
-    if let Ok(health) = health_query.single() {
-        let health_percent = (health.0 / 100.0).clamp(0.0, 1.0);
+    if !active.is_changed() {
+        return;
+    }
+    let node = active.0.as_ref().and_then(|conversation| {
+        let definition = library.get(&definitions, &conversation.dialogue)?;
+        Some((conversation, definition.nodes.get(&conversation.node)?))
+    });
+    for mut visibility in &mut box_query {
+        *visibility = if node.is_some() {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+    let (Some((conversation, node)), Ok(choices)) = (node, choices_query.single()) else {
+        return;
+    };
 
-        // Update fill width
-        if let Ok(mut style) = fill_query.single_mut() {
-            style.width = Val::Percent(health_percent * 100.0);
-        }
+    if let Ok(mut speaker) = speaker_query.single_mut() {
+        speaker.0 = node.speaker.clone().unwrap_or_else(|| {
+            npc_query
+                .get(conversation.npc)
+                .map(|npc| npc.name.clone())
+                .unwrap_or_default()
+        });
+    }
+    if let Ok(mut text) = text_query.single_mut() {
+        text.0 = node.text.clone();
+    }
 
-        // Update text
-        if let Ok(mut text) = text_query.single_mut() {
-            text.0 = format!("{:.0}", health.0);
+    commands.entity(choices).despawn_related::<Children>();
+    let labels: Vec<String> = if conversation.choices.is_empty() {
+        vec![
+            if node.next.is_some() {
+                "Continue"
+            } else {
+                "Goodbye"
+            }
+            .to_string(),
+        ]
+    } else {
+        conversation
+            .choices
+            .iter()
+            .filter_map(|index| node.choices.get(*index))
+            .map(|choice| choice.text.clone())
+            .collect()
+    };
+    for (index, label) in labels.into_iter().enumerate() {
+        let color = if index == conversation.selected {
+            css::SLATE_GRAY
+        } else {
+            css::DIM_GRAY
+        };
+        commands.entity(choices).with_child((
+            Button,
+            Node {
+                padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                ..default()
+            },
+            BackgroundColor(Color::from(color)),
+            DialogueChoiceButton(index),
+            children![(
+                Text::new(format!("{}. {label}", index + 1)),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+            )],
+        ));
+    }
+}
+
+fn click_dialogue_choices(
+    mut choose: EventWriter<ChooseDialogueOption>,
+    mut button_query: Query<
+        (&Interaction, &DialogueChoiceButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, button, mut color) in &mut button_query {
+        color.0 = match interaction {
+            Interaction::Pressed => Color::from(css::GRAY),
+            Interaction::Hovered => Color::from(css::SLATE_GRAY),
+            Interaction::None => Color::from(css::DIM_GRAY),
+        };
+        if *interaction == Interaction::Pressed {
+            choose.write(ChooseDialogueOption(button.0));
         }
     }
 }
@@ -0,0 +1,227 @@
+"""Builds the stand-in character rigs in assets/characters.
+
+Box figures with an animated pivot node per limb and one clip for each
+`CharacterClip` name. Change the clips or proportions here, then run
+
+    python3 tools/make_rig.py assets/characters
+
+Swap in a proper skinned model from Blender by exporting over the same file
+names with the same clip names.
+"""
+
+import base64, json, math, struct, sys
+
+CLIPS = ["Idle", "Walk", "Run", "Jump", "Fall", "Crouch", "Dash", "Swim", "Cast", "Hit", "Death"]
+
+
+def quat_x(deg):
+    a = math.radians(deg) / 2
+    return [math.sin(a), 0.0, 0.0, math.cos(a)]
+
+
+def quat_z(deg):
+    a = math.radians(deg) / 2
+    return [0.0, 0.0, math.sin(a), math.cos(a)]
+
+
+def quat_mul(a, b):
+    ax, ay, az, aw = a
+    bx, by, bz, bw = b
+    return [
+        aw * bx + ax * bw + ay * bz - az * by,
+        aw * by - ax * bz + ay * bw + az * bx,
+        aw * bz + ax * by - ay * bx + az * bw,
+        aw * bw - ax * bx - ay * by - az * bz,
+    ]
+
+
+def build(path, colors, proportions):
+    body, skin, limb = colors
+    hip_height, torso, head, arm, leg, width = proportions
+    buf = bytearray()
+    views, accessors = [], []
+
+    def add(data, fmt, count, type_, target=None, minmax=None, component=5126):
+        while len(buf) % 4:
+            buf.append(0)
+        offset = len(buf)
+        buf.extend(struct.pack("<" + fmt * len(data), *data))
+        view = {"buffer": 0, "byteOffset": offset, "byteLength": len(buf) - offset}
+        if target:
+            view["target"] = target
+        views.append(view)
+        acc = {"bufferView": len(views) - 1, "componentType": component, "count": count, "type": type_}
+        if minmax:
+            acc["min"], acc["max"] = minmax
+        accessors.append(acc)
+        return len(accessors) - 1
+
+    # Unit cube, 4 verts per face so normals are flat
+    positions, normals, indices = [], [], []
+    for axis in range(3):
+        for sign in (-1, 1):
+            n = [0, 0, 0]
+            n[axis] = sign
+            u = [0, 0, 0]
+            v = [0, 0, 0]
+            u[(axis + 1) % 3] = 1
+            v[(axis + 2) % 3] = 1
+            base = len(positions)
+            for su, sv in ((-1, -1), (1, -1), (1, 1), (-1, 1)):
+                positions.append([n[i] * 0.5 + u[i] * su * 0.5 + v[i] * sv * 0.5 for i in range(3)])
+                normals.append(n)
+            quad = [0, 1, 2, 0, 2, 3] if sign > 0 else [0, 2, 1, 0, 3, 2]
+            indices += [base + i for i in quad]
+    flat = lambda rows: [x for row in rows for x in row]
+    pos_acc = add(flat(positions), "f", 24, "VEC3", 34962, ([-0.5] * 3, [0.5] * 3))
+    nrm_acc = add(flat(normals), "f", 24, "VEC3", 34962)
+    idx_acc = add(indices, "H", 36, "SCALAR", 34963, component=5123)
+
+    materials = [
+        {"name": name, "pbrMetallicRoughness": {"baseColorFactor": color + [1.0], "metallicFactor": 0.0, "roughnessFactor": 0.8}}
+        for name, color in (("Body", body), ("Skin", skin), ("Limb", limb))
+    ]
+    meshes = [
+        {"name": m["name"], "primitives": [{"attributes": {"POSITION": pos_acc, "NORMAL": nrm_acc}, "indices": idx_acc, "material": i}]}
+        for i, m in enumerate(materials)
+    ]
+
+    nodes = []
+
+    def node(name, translation=None, children=None, mesh=None, scale=None):
+        n = {"name": name}
+        if translation:
+            n["translation"] = translation
+        if children:
+            n["children"] = children
+        if mesh is not None:
+            n["mesh"] = mesh
+        if scale:
+            n["scale"] = scale
+        nodes.append(n)
+        return len(nodes) - 1
+
+    # Pivots hold the animation, the boxes hang off them
+    shape = lambda name, mesh, size, offset: node(name, translation=offset, mesh=mesh, scale=size)
+    torso_box = shape("TorsoShape", 0, [width, torso, width * 0.55], [0, torso / 2, 0])
+    head_box = shape("HeadShape", 1, [head, head, head], [0, head / 2, 0])
+    head_pivot = node("Head", [0, torso, 0], [head_box])
+    arms, legs = [], []
+    for side, x in (("L", 1), ("R", -1)):
+        box = shape(f"Arm{side}Shape", 2, [0.18, arm, 0.18], [0, -arm / 2, 0])
+        arms.append(node(f"Arm{side}", [x * (width / 2 + 0.1), torso * 0.92, 0], [box]))
+    chest = node("Chest", [0, 0, 0], [torso_box, head_pivot] + arms)
+    for side, x in (("L", 1), ("R", -1)):
+        box = shape(f"Leg{side}Shape", 2, [0.22, leg, 0.22], [0, -leg / 2, 0])
+        legs.append(node(f"Leg{side}", [x * width * 0.25, 0, 0], [box]))
+    hips = node("Hips", [0, hip_height, 0], [chest] + legs)
+    root = node("Character", children=[hips])
+    arm_l, arm_r = arms
+    leg_l, leg_r = legs
+
+    samplers_channels = []
+
+    def animation(name, length, tracks):
+        samplers, channels = [], []
+        for target, prop, keys in tracks:
+            times = [t * length for t, _ in keys]
+            values = flat(v for _, v in keys)
+            width_ = 4 if prop == "rotation" else 3
+            inp = add(times, "f", len(times), "SCALAR", minmax=([min(times)], [max(times)]))
+            out = add(values, "f", len(times), "VEC4" if width_ == 4 else "VEC3")
+            samplers.append({"input": inp, "output": out, "interpolation": "LINEAR"})
+            channels.append({"sampler": len(samplers) - 1, "target": {"node": target, "path": prop}})
+        samplers_channels.append({"name": name, "samplers": samplers, "channels": channels})
+
+    def swing(amount, phase=0.0, steps=8):
+        return [(i / steps, quat_x(amount * math.sin((i / steps + phase) * math.tau))) for i in range(steps + 1)]
+
+    def hold(q):
+        return [(0.0, q), (1.0, q)]
+
+    def bob(height, depth, steps=8, cycles=1):
+        return [(i / steps, [0, height - depth * (0.5 - 0.5 * math.cos(i / steps * math.tau * cycles)), 0]) for i in range(steps + 1)]
+
+    rest = quat_x(0)
+    animation("Idle", 2.0, [
+        (hips, "translation", bob(hip_height, 0.03)),
+        (arm_l, "rotation", swing(4)),
+        (arm_r, "rotation", swing(4, 0.5)),
+    ])
+    for name, length, amount, lean in (("Walk", 1.0, 30, 4), ("Run", 0.6, 50, 12)):
+        animation(name, length, [
+            (leg_l, "rotation", swing(amount)),
+            (leg_r, "rotation", swing(amount, 0.5)),
+            (arm_l, "rotation", swing(amount * 0.8, 0.5)),
+            (arm_r, "rotation", swing(amount * 0.8)),
+            (chest, "rotation", hold(quat_x(lean))),
+            (hips, "translation", bob(hip_height, 0.06, cycles=2)),
+        ])
+    animation("Jump", 0.4, [
+        (leg_l, "rotation", [(0, rest), (1, quat_x(-40))]),
+        (leg_r, "rotation", [(0, rest), (1, quat_x(20))]),
+        (arm_l, "rotation", [(0, rest), (1, quat_z(150))]),
+        (arm_r, "rotation", [(0, rest), (1, quat_z(-150))]),
+    ])
+    animation("Fall", 1.0, [
+        (arm_l, "rotation", [(t, quat_z(110 + 10 * math.sin(t * math.tau))) for t in (0, 0.25, 0.5, 0.75, 1)]),
+        (arm_r, "rotation", [(t, quat_z(-110 - 10 * math.sin(t * math.tau))) for t in (0, 0.25, 0.5, 0.75, 1)]),
+        (leg_l, "rotation", hold(quat_x(-15))),
+        (leg_r, "rotation", hold(quat_x(10))),
+    ])
+    animation("Crouch", 1.0, [
+        (hips, "translation", hold([0, hip_height * 0.6, 0])),
+        (leg_l, "rotation", hold(quat_x(-60))),
+        (leg_r, "rotation", hold(quat_x(-60))),
+        (chest, "rotation", hold(quat_x(25))),
+    ])
+    animation("Dash", 0.3, [
+        (chest, "rotation", hold(quat_x(35))),
+        (arm_l, "rotation", hold(quat_x(60))),
+        (arm_r, "rotation", hold(quat_x(60))),
+        (leg_l, "rotation", hold(quat_x(-30))),
+        (leg_r, "rotation", hold(quat_x(40))),
+    ])
+    animation("Swim", 1.2, [
+        (chest, "rotation", hold(quat_x(70))),
+        (arm_l, "rotation", [(i / 8, quat_x(-360 * i / 8)) for i in range(9)]),
+        (arm_r, "rotation", [(i / 8, quat_x(-360 * i / 8 - 180)) for i in range(9)]),
+        (leg_l, "rotation", swing(20, steps=4)),
+        (leg_r, "rotation", swing(20, 0.5, steps=4)),
+    ])
+    # The release is timed at 0.25 s in by CharacterRig::cast_release
+    animation("Cast", 0.6, [
+        (arm_r, "rotation", [(0, rest), (0.35, quat_x(-60)), (0.45, quat_x(-95)), (1, rest)]),
+        (chest, "rotation", [(0, rest), (0.35, quat_mul(quat_x(-5), [0, math.sin(-0.15), 0, math.cos(-0.15)])), (1, rest)]),
+    ])
+    animation("Hit", 0.35, [
+        (chest, "rotation", [(0, rest), (0.3, quat_x(-25)), (1, rest)]),
+        (head_pivot, "rotation", [(0, rest), (0.3, quat_x(-20)), (1, rest)]),
+    ])
+    animation("Death", 1.0, [
+        (hips, "rotation", [(0, rest), (0.6, quat_x(-80)), (1, quat_x(-90))]),
+        (hips, "translation", [(0, [0, hip_height, 0]), (1, [0, 0.25, 0])]),
+        (arm_l, "rotation", [(0, rest), (1, quat_z(60))]),
+        (arm_r, "rotation", [(0, rest), (1, quat_z(-60))]),
+    ])
+    assert [a["name"] for a in samplers_channels] == CLIPS
+
+    gltf = {
+        "asset": {"version": "2.0", "generator": "make_rig.py"},
+        "scene": 0,
+        "scenes": [{"nodes": [root]}],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": materials,
+        "animations": samplers_channels,
+        "accessors": accessors,
+        "bufferViews": views,
+        "buffers": [{"byteLength": len(buf), "uri": "data:application/octet-stream;base64," + base64.b64encode(bytes(buf)).decode()}],
+    }
+    with open(path, "w") as f:
+        json.dump(gltf, f, separators=(",", ":"))
+
+
+# colors: body, skin, limbs. proportions: hip height, torso, head, arm, leg, shoulder width
+build(sys.argv[1] + "/player.gltf", ([0.15, 0.25, 0.6], [0.9, 0.72, 0.55], [0.2, 0.18, 0.22]), (0.95, 0.75, 0.3, 0.7, 0.9, 0.5))
+build(sys.argv[1] + "/creep.gltf", ([0.35, 0.45, 0.15], [0.5, 0.6, 0.25], [0.25, 0.3, 0.1]), (0.8, 0.9, 0.45, 0.85, 0.75, 0.8))